@@ -0,0 +1,190 @@
+//! Animation layers: clips played on top of the base animation, limited to some of the bones.
+//!
+//! Layers are declared on the root of a model next to, or instead of, its state machine. Each
+//! layer's clip is found by name in the model's `ClipLibrary` and played with zero weight from
+//! a node of the layer's own, so Bevy keeps its time but does not apply it, and a layer can use
+//! a clip the base animation plays too. Once the base pose is sampled, every layer is sampled
+//! at that time on the bones of its mask and blended in, in order.
+
+use bevy::animation::AnimationTarget;
+use bevy::prelude::*;
+use crate::animator::PostAnimationSet;
+use crate::clip_library::ClipLibrary;
+use crate::clip_sampling::{sample_rotation, sample_scale, sample_translation};
+
+#[derive(Reflect, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum LayerBlend {
+    // Replaces the pose below, by the layer's weight
+    #[default]
+    Override,
+    // Adds the clip's change from its first frame to the pose below
+    Additive,
+}
+
+// Bones of a model a layer applies to, by `Name`
+#[derive(Reflect, Clone, Default, Debug)]
+pub struct BoneMask {
+    // Bones included on their own
+    pub bones: Vec<String>,
+    // Bones included together with everything below them, like "mixamorig:Spine1"
+    pub subtrees: Vec<String>,
+}
+
+impl BoneMask {
+    fn resolve(&self, root: Entity, children: &Query<&Children>, names: &Query<&Name>) -> Vec<Entity> {
+        let named = |list: &Vec<String>, entity: Entity| {
+            names.get(entity).is_ok_and(|name| list.iter().any(|bone| bone == name.as_str()))
+        };
+        let mut masked: Vec<Entity> = children
+            .iter_descendants(root)
+            .filter(|entity| named(&self.bones, *entity))
+            .collect();
+        for subtree in children.iter_descendants(root).filter(|entity| named(&self.subtrees, *entity)) {
+            masked.push(subtree);
+            masked.extend(children.iter_descendants(subtree));
+        }
+        masked.sort_unstable();
+        masked.dedup();
+        masked
+    }
+}
+
+#[derive(Reflect, Clone, Debug)]
+pub struct AnimationLayer {
+    // Name of the clip in the model's `ClipLibrary`
+    pub clip: String,
+    pub mask: BoneMask,
+    pub weight: f32,
+    pub blend: LayerBlend,
+    pub speed: f32,
+}
+
+impl Default for AnimationLayer {
+    fn default() -> Self {
+        AnimationLayer {
+            clip: String::new(),
+            mask: BoneMask::default(),
+            weight: 1.0,
+            blend: LayerBlend::default(),
+            speed: 1.0,
+        }
+    }
+}
+
+// Layers are blended in order, each on top of the result of the ones before it
+#[derive(Component, Reflect, Clone, Default, Debug)]
+#[reflect(Component)]
+pub struct AnimationLayers {
+    pub layers: Vec<AnimationLayer>,
+}
+
+// Graph node of a layer's clip and the bones it resolved to
+struct LayerNodes {
+    node: AnimationNodeIndex,
+    clip: Handle<AnimationClip>,
+    masked: Option<Vec<Entity>>,
+}
+
+// Runtime state of the layers on one `AnimationPlayer`
+#[derive(Component)]
+pub struct ActiveLayers {
+    owner: Entity,
+    layers: Vec<LayerNodes>,
+}
+
+// Adds a node to `graph` for each layer's clip, found in `library`, and starts it without
+// weight. Every clip must be in the library.
+pub(crate) fn start_layers(
+    owner: Entity,
+    layers: &AnimationLayers,
+    library: &ClipLibrary,
+    graph: &mut AnimationGraph,
+    player: &mut AnimationPlayer,
+) -> ActiveLayers {
+    let layers = layers
+        .layers
+        .iter()
+        .filter_map(|layer| {
+            let clip = library.clip(&layer.clip)?.clone();
+            let node = graph.add_clip(clip.clone(), 1.0, graph.root);
+            let animation = player.start(node);
+            animation.set_weight(0.0);
+            animation.set_speed(layer.speed).repeat();
+            Some(LayerNodes { node, clip, masked: None })
+        })
+        .collect();
+    ActiveLayers { owner, layers }
+}
+
+fn blend_layers(
+    clips: Res<Assets<AnimationClip>>,
+    owners: Query<&AnimationLayers>,
+    children: Query<&Children>,
+    names: Query<&Name>,
+    mut players: Query<(Entity, &mut ActiveLayers, &AnimationPlayer)>,
+    mut bones: Query<(&AnimationTarget, &mut Transform)>,
+) {
+    for (entity, mut active, player) in players.iter_mut() {
+        let Ok(declared) = owners.get(active.owner) else {
+            continue;
+        };
+        let owner = active.owner;
+        for (nodes, layer) in active.layers.iter_mut().zip(&declared.layers) {
+            let weight = layer.weight.clamp(0.0, 1.0);
+            let (Some(clip), Some(animation)) = (clips.get(&nodes.clip), player.animation(nodes.node)) else {
+                continue;
+            };
+            if weight == 0.0 {
+                continue;
+            }
+            let time = animation.seek_time();
+            let masked = nodes.masked.get_or_insert_with(|| layer.mask.resolve(owner, &children, &names));
+
+            for bone in masked.iter() {
+                let Ok((target, mut transform)) = bones.get_mut(*bone) else {
+                    continue;
+                };
+                if target.player != entity {
+                    continue;
+                }
+                let Some(curves) = clip.curves_for_target(target.id) else {
+                    continue;
+                };
+                match layer.blend {
+                    LayerBlend::Override => {
+                        if let Some(translation) = sample_translation(curves, time) {
+                            transform.translation = transform.translation.lerp(translation, weight);
+                        }
+                        if let Some(rotation) = sample_rotation(curves, time) {
+                            transform.rotation = transform.rotation.slerp(rotation, weight);
+                        }
+                        if let Some(scale) = sample_scale(curves, time) {
+                            transform.scale = transform.scale.lerp(scale, weight);
+                        }
+                    }
+                    LayerBlend::Additive => {
+                        if let (Some(translation), Some(reference)) = (sample_translation(curves, time), sample_translation(curves, 0.0)) {
+                            transform.translation += (translation - reference) * weight;
+                        }
+                        if let (Some(rotation), Some(reference)) = (sample_rotation(curves, time), sample_rotation(curves, 0.0)) {
+                            let change = Quat::IDENTITY.slerp(reference.inverse() * rotation, weight);
+                            transform.rotation = (transform.rotation * change).normalize();
+                        }
+                        if let (Some(scale), Some(reference)) = (sample_scale(curves, time), sample_scale(curves, 0.0)) {
+                            transform.scale *= Vec3::ONE.lerp(scale / reference, weight);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub struct EzAnimationLayerPlugin;
+
+impl Plugin for EzAnimationLayerPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<AnimationLayers>()
+            .add_systems(PostUpdate, blend_layers.in_set(PostAnimationSet::Pose));
+    }
+}
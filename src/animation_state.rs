@@ -0,0 +1,350 @@
+//! Saving and restoring the phase of every running animation.
+//!
+//! The snapshot holds the shared timeline, every named entity's own timeline and what every
+//! named `AnimationPlayer` plays: the current state of its state machine and that state's clips,
+//! or else the main animation of its `AnimationTransitions`. Entities are matched by the path of
+//! `Name`s from their outermost named ancestor, and clips by their names in the model's
+//! `ClipLibrary`, since entity ids and graph nodes change between sessions and glTF scenes are
+//! respawned. The snapshot is a `DynamicScene` serialised through the type registry, with the
+//! shared timeline as its resource and a `SavedAnimationState` for each entity. State machines
+//! jump to their saved state without a crossfade and other players play their saved clip
+//! through their transitions, so their completion counts start over. The state is
+//! saved when leaving `GameState::Playing`, on exit and with F5, and is restored on entering
+//! `GameState::Playing` and with F9. Outside the web it is also written to a file, so it lasts
+//! between sessions.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs;
+use bevy::animation::{ActiveAnimation, RepeatAnimation};
+use bevy::ecs::system::SystemParam;
+use bevy::input::common_conditions::input_just_pressed;
+use bevy::prelude::*;
+use bevy::scene::serde::SceneDeserializer;
+use bevy::scene::{ron, DynamicEntity};
+use serde::de::DeserializeSeed;
+use crate::GameState;
+use crate::clip_library::{ClipLibraries, ClipLibrary};
+use crate::loading::AnimationAssets;
+use crate::scene_authoring::GltfScene;
+use crate::state_machine::{ActiveStateMachine, AnimationStateMachine};
+use crate::timeline::Timeline;
+
+#[cfg(not(target_arch = "wasm32"))]
+const SAVE_PATH: &str = "animation_state.ron";
+
+// Saved state of one named entity
+#[derive(Component, Reflect, Clone, Default)]
+#[reflect(Component)]
+struct SavedAnimationState {
+    path: String,
+    timeline: Option<Timeline>,
+    // Current state of the player's state machine, if it has one
+    state: Option<String>,
+    animations: Vec<PlayingAnimation>,
+}
+
+// One active animation of an `AnimationPlayer`, identified by the name of its clip
+#[derive(Reflect, Clone)]
+struct PlayingAnimation {
+    clip: String,
+    speed: f32,
+    seek_time: f32,
+    repeat: RepeatAnimation,
+    paused: bool,
+}
+
+// The last snapshot taken, which is all there is to restore from on the web
+#[derive(Resource, Default)]
+struct AnimationStateStore(Option<String>);
+
+// Parts of the snapshot still waiting for their entities, which may be spawned some frames after
+// the state is loaded
+#[derive(Resource)]
+struct PendingAnimationState {
+    timelines: BTreeMap<String, Timeline>,
+    players: BTreeMap<String, (Option<String>, Vec<PlayingAnimation>)>,
+}
+
+#[derive(SystemParam)]
+struct EntityPaths<'w, 's> {
+    names: Query<'w, 's, &'static Name>,
+    parents: Query<'w, 's, &'static Parent>,
+}
+
+impl EntityPaths<'_, '_> {
+    // Names of the entity and its named ancestors, outermost first, e.g. "Walker/Armature".
+    // Unnamed entities have no path and are not saved.
+    fn path(&self, entity: Entity) -> Option<String> {
+        let mut path = vec![self.names.get(entity).ok()?.as_str()];
+        let mut current = entity;
+        while let Ok(parent) = self.parents.get(current) {
+            current = parent.get();
+            if let Ok(name) = self.names.get(current) {
+                path.push(name.as_str());
+            }
+        }
+        path.reverse();
+        Some(path.join("/"))
+    }
+}
+
+// Clip library of each player's model, found the way `play_animations` finds it
+#[derive(SystemParam)]
+struct PlayerLibraries<'w, 's> {
+    libraries: Res<'w, ClipLibraries>,
+    animations: Res<'w, AnimationAssets>,
+    scenes: Query<'w, 's, &'static GltfScene>,
+    parents: Query<'w, 's, &'static Parent>,
+}
+
+impl PlayerLibraries<'_, '_> {
+    fn library(&self, player: Entity) -> Option<&ClipLibrary> {
+        // Players outside scene files belong to the walker
+        match self.parents.iter_ancestors(player).find_map(|ancestor| self.scenes.get(ancestor).ok()) {
+            Some(scene) => self.libraries.for_scene(&scene.path),
+            None => self.libraries.get(&self.animations.walker.path()?.to_string()),
+        }
+    }
+}
+
+fn playing_animation(clip: &str, animation: &ActiveAnimation) -> PlayingAnimation {
+    PlayingAnimation {
+        clip: clip.to_string(),
+        speed: animation.speed(),
+        seek_time: animation.seek_time(),
+        repeat: animation.repeat_mode(),
+        paused: animation.is_paused(),
+    }
+}
+
+// Picks up a saved animation where it was. Weights are left to the transitions and the state
+// machine.
+fn resume_animation(animation: &mut ActiveAnimation, saved: &PlayingAnimation) {
+    animation
+        .set_speed(saved.speed)
+        .set_repeat(saved.repeat)
+        .seek_to(saved.seek_time);
+    if saved.paused {
+        animation.pause();
+    } else {
+        animation.resume();
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_snapshot(text: &str) -> Result<(), String> {
+    fs::write(SAVE_PATH, text).map_err(|error| error.to_string())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write_snapshot(_text: &str) -> Result<(), String> {
+    Ok(())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_snapshot() -> Option<String> {
+    fs::read_to_string(SAVE_PATH).ok()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read_snapshot() -> Option<String> {
+    None
+}
+
+#[allow(clippy::too_many_arguments)]
+fn save_animation_state(
+    shared: Res<Timeline>,
+    timelines: Query<(Entity, &Timeline)>,
+    players: Query<(Entity, &AnimationPlayer, &AnimationTransitions, &Handle<AnimationGraph>, Option<&ActiveStateMachine>)>,
+    machines: Query<&AnimationStateMachine>,
+    graphs: Res<Assets<AnimationGraph>>,
+    libraries: PlayerLibraries,
+    paths: EntityPaths,
+    registry: Res<AppTypeRegistry>,
+    mut store: ResMut<AnimationStateStore>,
+) {
+    let mut saved: BTreeMap<Entity, SavedAnimationState> = BTreeMap::new();
+    for (entity, timeline) in timelines.iter() {
+        if let Some(path) = paths.path(entity) {
+            saved.entry(entity).or_insert_with(|| SavedAnimationState { path, ..default() }).timeline = Some(timeline.clone());
+        }
+    }
+    for (entity, player, transitions, graph, active) in players.iter() {
+        let (Some(path), Some(library)) = (paths.path(entity), libraries.library(entity)) else {
+            continue;
+        };
+        // A machine's player saves the clips of its current state, others their main animation
+        let (state, clips) = match active.and_then(|active| Some((active, machines.get(active.owner()).ok()?))) {
+            Some((active, machine)) => (
+                active.current_state(machine).map(str::to_string),
+                active.current_clips().iter().map(|(node, clip)| (*node, clip.clone())).collect(),
+            ),
+            None => {
+                let main = transitions.get_main_animation().and_then(|node| {
+                    Some((node, graphs.get(graph)?.get(node)?.clip.clone()?))
+                });
+                (None, main.into_iter().collect::<Vec<_>>())
+            }
+        };
+        let animations = clips
+            .iter()
+            .filter_map(|(node, clip)| Some(playing_animation(library.name(clip)?, player.animation(*node)?)))
+            .collect();
+        let saved = saved.entry(entity).or_insert_with(|| SavedAnimationState { path, ..default() });
+        saved.state = state;
+        saved.animations = animations;
+    }
+    let snapshot = DynamicScene {
+        resources: vec![Box::new(shared.clone())],
+        entities: saved
+            .into_iter()
+            .map(|(entity, saved)| DynamicEntity { entity, components: vec![Box::new(saved)] })
+            .collect(),
+    };
+
+    let result = snapshot
+        .serialize(&registry.read())
+        .map_err(|error| error.to_string())
+        .and_then(|text| {
+            let written = write_snapshot(&text);
+            store.0 = Some(text);
+            written
+        });
+    match result {
+        Ok(()) => info!("saved animation state"),
+        Err(error) => warn!("could not save animation state: {error}"),
+    }
+}
+
+fn load_animation_state(mut commands: Commands, registry: Res<AppTypeRegistry>, store: Res<AnimationStateStore>) {
+    let Some(text) = store.0.clone().or_else(read_snapshot) else {
+        return;
+    };
+    let registry = registry.read();
+    let snapshot = ron::de::Deserializer::from_str(&text)
+        .map_err(|error| error.to_string())
+        .and_then(|mut deserializer| {
+            SceneDeserializer { type_registry: &registry }
+                .deserialize(&mut deserializer)
+                .map_err(|error| error.to_string())
+        });
+    let snapshot = match snapshot {
+        Ok(snapshot) => snapshot,
+        Err(error) => {
+            warn!("could not load animation state: {error}");
+            return;
+        }
+    };
+
+    if let Some(shared) = snapshot.resources.iter().find_map(|resource| Timeline::from_reflect(resource.as_ref())) {
+        commands.insert_resource(shared);
+    }
+    let mut pending = PendingAnimationState {
+        timelines: BTreeMap::new(),
+        players: BTreeMap::new(),
+    };
+    let saved = snapshot
+        .entities
+        .iter()
+        .flat_map(|entity| &entity.components)
+        .filter_map(|component| SavedAnimationState::from_reflect(component.as_ref()));
+    for saved in saved {
+        if let Some(timeline) = saved.timeline {
+            pending.timelines.insert(saved.path.clone(), timeline);
+        }
+        if !saved.animations.is_empty() {
+            pending.players.insert(saved.path, (saved.state, saved.animations));
+        }
+    }
+    commands.insert_resource(pending);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn restore_animation_state(
+    mut commands: Commands,
+    mut pending: ResMut<PendingAnimationState>,
+    mut timelines: Query<(Entity, &mut Timeline)>,
+    // Players are restored once their graph is attached, after `play_animations` started them
+    mut players: Query<(Entity, &mut AnimationPlayer, &mut AnimationTransitions, Option<&mut ActiveStateMachine>), With<Handle<AnimationGraph>>>,
+    machines: Query<&AnimationStateMachine>,
+    libraries: PlayerLibraries,
+    paths: EntityPaths,
+) {
+    for (entity, mut timeline) in timelines.iter_mut() {
+        if let Some(saved) = paths.path(entity).and_then(|path| pending.timelines.remove(&path)) {
+            *timeline = saved;
+        }
+    }
+
+    for (entity, mut player, mut transitions, active) in players.iter_mut() {
+        let Some((state, animations)) = paths.path(entity).and_then(|path| pending.players.remove(&path)) else {
+            continue;
+        };
+        let machine = active.and_then(|active| Some((machines.get(active.owner()).ok()?, active)));
+        match (machine, state) {
+            (Some((machine, mut active)), Some(state)) => {
+                let Some(clips) = active.jump_to_state(machine, &state, &mut transitions, &mut player) else {
+                    warn!("{entity} has no animation state {state:?} to restore");
+                    continue;
+                };
+                // Each saved clip goes back to the first node of the state that plays it and
+                // has none yet, so samples sharing a clip keep their order
+                let library = libraries.library(entity);
+                let mut animations: Vec<Option<PlayingAnimation>> = animations.into_iter().map(Some).collect();
+                for (node, clip) in clips {
+                    let name = library.and_then(|library| library.name(clip));
+                    let saved = animations
+                        .iter_mut()
+                        .find(|saved| saved.as_ref().is_some_and(|saved| Some(saved.clip.as_str()) == name));
+                    if let Some(saved) = saved.and_then(Option::take) {
+                        resume_animation(player.start(*node), &saved);
+                    }
+                }
+            }
+            (None, None) => {
+                let Some(saved) = animations.first() else {
+                    continue;
+                };
+                let Some(node) = libraries.library(entity).and_then(|library| library.node(&saved.clip)) else {
+                    warn!("{entity} has no clip {:?} to restore", saved.clip);
+                    continue;
+                };
+                let animation = transitions.play(&mut player, node, Duration::ZERO);
+                animation.replay();
+                resume_animation(animation, saved);
+            }
+            // Saved with a state machine and restored without one, or the other way around
+            _ => warn!("{entity} no longer plays its saved animation state"),
+        }
+    }
+
+    if pending.timelines.is_empty() && pending.players.is_empty() {
+        commands.remove_resource::<PendingAnimationState>();
+    }
+}
+
+fn discard_pending_animation_state(mut commands: Commands) {
+    commands.remove_resource::<PendingAnimationState>();
+}
+
+pub struct EzAnimationStatePlugin;
+
+impl Plugin for EzAnimationStatePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<SavedAnimationState>()
+            .init_resource::<AnimationStateStore>()
+            .add_systems(OnEnter(GameState::Playing), load_animation_state)
+            .add_systems(OnExit(GameState::Playing), (save_animation_state, discard_pending_animation_state))
+            .add_systems(Update,
+                         (
+                             save_animation_state.run_if(input_just_pressed(KeyCode::F5)),
+                             load_animation_state.run_if(input_just_pressed(KeyCode::F9)),
+                             restore_animation_state.run_if(resource_exists::<PendingAnimationState>),
+                         ).chain().run_if(in_state(GameState::Playing)))
+            .add_systems(Last, save_animation_state
+                .run_if(on_event::<AppExit>())
+                .run_if(in_state(GameState::Playing)));
+    }
+}
@@ -5,12 +5,36 @@ use std::path::PathBuf;
 use std::time::Duration;
 
 use bevy::{
+    animation::animate_targets,
     prelude::*,
+    transform::TransformSystem,
 };
 use bevy::asset::AssetContainer;
 use bevy::ecs::observer::TriggerTargets;
+use bevy::utils::HashSet;
 use crate::GameState;
 use crate::loading::AnimationAssets;
+use crate::animation_layers::{start_layers, AnimationLayers};
+use crate::clip_library::ClipLibraries;
+use crate::gait::ProceduralGait;
+use crate::scene_authoring::GltfScene;
+use crate::state_machine::{start_state_machine, AnimationStateMachine};
+
+// Systems that adjust the sampled pose of skeletons before transforms are propagated, run in
+// the order of the variants
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PostAnimationSet {
+    // Blending more into the sampled pose
+    Pose,
+    // Constraint stacks adjusting the blended pose
+    Constraints,
+    // Moving the model itself, e.g. by root motion
+    Motion,
+    // Inverse kinematics reacting to where the model and its bones ended up
+    Ik,
+    // Simulated follow-through on top of the finished pose
+    Secondary,
+}
 
 #[derive(Resource)]
 struct Animations {
@@ -19,33 +43,78 @@ struct Animations {
     graph: Handle<AnimationGraph>,
 }
 
-// System to play animation
+// System to play animation. Players wait for the clip library of their model to be built.
+// Players below an `AnimationStateMachine` are handed to it, any others loop the first clip of
+// the library from its shared graph, unless a `ProceduralGait` poses their model instead. `AnimationLayers` on an ancestor play on top of either.
+// Players with a machine or layers get their own copy of the library's graph, with a node for
+// every state, blend-space sample and layer, so each of those keeps its own time and weight
+// even when several play the same clip. Players whose machine or layers name clips the library
+// doesn't have yet, e.g. ones still being retargeted, wait for them.
+#[allow(clippy::too_many_arguments)]
 fn play_animations(
     mut commands: Commands,
     animations: Res<AnimationAssets>,
-    animation_clips: Res<Assets<AnimationClip>>,
+    libraries: Res<ClipLibraries>,
     mut graphs: ResMut<Assets<AnimationGraph>>,
-    mut players: Query<(Entity, &mut AnimationPlayer), Added<AnimationPlayer>>,
+    machines: Query<&AnimationStateMachine>,
+    layers: Query<&AnimationLayers>,
+    gaits: Query<(), With<ProceduralGait>>,
+    scenes: Query<&GltfScene>,
+    parents: Query<&Parent>,
+    mut players: Query<(Entity, &mut AnimationPlayer), Without<Handle<AnimationGraph>>>,
+    // Players already warned about missing clips
+    mut warned: Local<HashSet<Entity>>,
 ) {
-    // Check if the animation clip is loaded
-    if let Some(walker_clip) = animation_clips.get(&animations.walker_walk) {
-        // Create a new animation graph
-        let mut graph = AnimationGraph::new();
-        let animation_node = graph.add_clip(animations.walker_walk.clone(), 1.0, graph.root);
+    for (entity, mut player) in players.iter_mut() {
+        // Players outside scene files belong to the walker
+        let library = match parents.iter_ancestors(entity).find_map(|ancestor| scenes.get(ancestor).ok()) {
+            Some(scene) => libraries.for_scene(&scene.path),
+            None => animations.walker.path().and_then(|path| libraries.get(&path.to_string())),
+        };
+        let Some(library) = library else {
+            continue;
+        };
+        let machine = std::iter::once(entity)
+            .chain(parents.iter_ancestors(entity))
+            .find_map(|ancestor| Some((ancestor, machines.get(ancestor).ok()?)));
+        let layers = std::iter::once(entity)
+            .chain(parents.iter_ancestors(entity))
+            .find_map(|ancestor| Some((ancestor, layers.get(ancestor).ok()?)));
+        let gait = std::iter::once(entity)
+            .chain(parents.iter_ancestors(entity))
+            .any(|ancestor| gaits.contains(ancestor));
 
-        // Add the graph to the asset server and get its handle
-        let graph_handle = graphs.add(graph);
+        let missing: Vec<&str> = machine
+            .iter()
+            .flat_map(|(_, machine)| machine.clip_names())
+            .chain(layers.iter().flat_map(|(_, layers)| layers.layers.iter().map(|layer| layer.clip.as_str())))
+            .filter(|name| library.node(name).is_none())
+            .collect();
+        if !missing.is_empty() {
+            if warned.insert(entity) {
+                warn!("waiting for clips {missing:?} to animate {entity}");
+            }
+            continue;
+        }
 
-        // Loop through each player and play the animation
-        for (entity, mut player) in players.iter_mut() {
+        let Some(mut graph) = graphs.get(&library.graph).cloned() else {
+            continue;
+        };
+        if let Some((owner, machine)) = machine {
+            let (active, transitions) = start_state_machine(owner, machine, library, &mut graph, &mut player);
+            commands.entity(entity).insert((active, transitions));
+        } else if let Some(node) = library.first().filter(|_| !gait) {
             // Use AnimationTransitions to manage the animation
             let mut transitions = AnimationTransitions::new();
-            transitions.play(&mut player, animation_node, Duration::ZERO).repeat();
-
-            commands.entity(entity)
-                .insert(graph_handle.clone())
-                .insert(transitions);
+            transitions.play(&mut player, node, Duration::ZERO).repeat();
+            commands.entity(entity).insert(transitions);
+        }
+        if let Some((owner, layers)) = layers {
+            let active = start_layers(owner, layers, library, &mut graph, &mut player);
+            commands.entity(entity).insert(active);
         }
+        let graph = if machine.is_some() || layers.is_some() { graphs.add(graph) } else { library.graph.clone() };
+        commands.entity(entity).insert(graph);
     }
 }
 pub struct EzAnimationPlugin;
@@ -53,6 +122,10 @@ pub struct EzAnimationPlugin;
 impl Plugin for EzAnimationPlugin {
     fn build(&self, app: &mut App) {
         app
+            .configure_sets(PostUpdate, (PostAnimationSet::Pose, PostAnimationSet::Constraints, PostAnimationSet::Motion, PostAnimationSet::Ik, PostAnimationSet::Secondary)
+                .chain()
+                .after(animate_targets)
+                .before(TransformSystem::TransformPropagate))
             .add_systems(Update, play_animations.run_if(in_state(GameState::Playing)));
     }
 }
\ No newline at end of file
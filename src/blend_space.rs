@@ -0,0 +1,101 @@
+//! Blend spaces: several clips placed along one or two parameters, weighted by where the
+//! parameters currently are.
+
+use bevy::prelude::*;
+
+#[derive(Reflect, Clone, Default, Debug)]
+pub struct BlendSample1d {
+    // Name of the clip in the model's `ClipLibrary`
+    pub clip: String,
+    pub position: f32,
+    // Playback speed of the clip at this sample, so one clip can stand in for several gaits
+    pub speed: f32,
+}
+
+#[derive(Reflect, Clone, Default, Debug)]
+pub struct BlendSpace1d {
+    pub parameter: String,
+    pub samples: Vec<BlendSample1d>,
+}
+
+impl BlendSpace1d {
+    // Linear weights between the two samples either side of `value`, in sample order. Values
+    // outside the samples take the nearest one.
+    pub fn weights(&self, value: f32) -> Vec<f32> {
+        let mut weights = vec![0.0; self.samples.len()];
+        let mut below: Option<usize> = None;
+        let mut above: Option<usize> = None;
+        for (index, sample) in self.samples.iter().enumerate() {
+            if sample.position <= value && below.is_none_or(|i| sample.position > self.samples[i].position) {
+                below = Some(index);
+            }
+            if sample.position >= value && above.is_none_or(|i| sample.position < self.samples[i].position) {
+                above = Some(index);
+            }
+        }
+
+        match (below, above) {
+            (Some(below), Some(above)) if self.samples[above].position > self.samples[below].position => {
+                let (start, end) = (self.samples[below].position, self.samples[above].position);
+                let t = (value - start) / (end - start);
+                weights[below] = 1.0 - t;
+                weights[above] = t;
+            }
+            (Some(index), _) | (None, Some(index)) => weights[index] = 1.0,
+            (None, None) => {}
+        }
+        weights
+    }
+}
+
+#[derive(Reflect, Clone, Default, Debug)]
+pub struct BlendSample2d {
+    // Name of the clip in the model's `ClipLibrary`
+    pub clip: String,
+    pub position: Vec2,
+    pub speed: f32,
+}
+
+#[derive(Reflect, Clone, Default, Debug)]
+pub struct BlendSpace2d {
+    pub x_parameter: String,
+    pub y_parameter: String,
+    pub samples: Vec<BlendSample2d>,
+}
+
+impl BlendSpace2d {
+    // Gradient band interpolation: each sample's weight falls off linearly towards every other
+    // sample, and the smallest of those falloffs is kept. Works for any scattered layout and
+    // gives exactly one sample full weight at its own position.
+    pub fn weights(&self, point: Vec2) -> Vec<f32> {
+        let mut weights: Vec<f32> = self
+            .samples
+            .iter()
+            .enumerate()
+            .map(|(i, sample)| {
+                let offset = point - sample.position;
+                self.samples
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, _)| *j != i)
+                    .map(|(_, other)| {
+                        let edge = other.position - sample.position;
+                        let length_squared = edge.length_squared();
+                        if length_squared <= f32::EPSILON {
+                            1.0
+                        } else {
+                            1.0 - offset.dot(edge) / length_squared
+                        }
+                    })
+                    .fold(1.0f32, f32::min)
+                    .max(0.0)
+            })
+            .collect();
+
+        let total: f32 = weights.iter().sum();
+        if total > f32::EPSILON {
+            weights.iter_mut().for_each(|weight| *weight /= total);
+        }
+        weights
+    }
+}
@@ -0,0 +1,163 @@
+//! Events authored on skeletal clips, like footsteps, fired when playback passes them.
+//!
+//! Events sit at normalised times of a clip, named as in the model's `ClipLibrary`, and are
+//! declared on the root of a model. Every frame the span each playing clip covered since the
+//! last frame is checked for events, including whole cycles when the clip looped more than
+//! once, backwards when it plays in reverse. Every state, blend-space sample and layer plays its
+//! clip from a node of its own, so a player's nodes often play the same clip in step, and only
+//! the most heavily weighted of them fires.
+
+use bevy::animation::ActiveAnimation;
+use bevy::prelude::*;
+use bevy::reflect::GetPath;
+use bevy::utils::HashMap;
+use crate::animator::PostAnimationSet;
+use crate::clip_library::ClipLibraries;
+use crate::scene_authoring::GltfScene;
+
+#[derive(Reflect, Clone, Default, Debug)]
+pub struct ClipEventKey {
+    // Normalised time within the clip, from 0 to 1
+    pub time: f32,
+    pub payload: String,
+}
+
+#[derive(Reflect, Clone, Default, Debug)]
+pub struct ClipEventTrack {
+    // Name of the clip in the model's `ClipLibrary`
+    pub clip: String,
+    pub events: Vec<ClipEventKey>,
+}
+
+#[derive(Component, Reflect, Clone, Default, Debug)]
+#[reflect(Component)]
+pub struct ClipEvents {
+    pub tracks: Vec<ClipEventTrack>,
+}
+
+// Fired for the entity holding the `ClipEvents`, for audio and effects to read
+#[allow(dead_code)]
+#[derive(Event, Clone, Debug)]
+pub struct ClipEvent {
+    pub entity: Entity,
+    pub clip: String,
+    pub payload: String,
+}
+
+// Clip name and handle of each track and the playback position of each player's graph nodes
+// last frame
+#[derive(Component, Default)]
+struct ClipEventState {
+    clips: Vec<(String, Handle<AnimationClip>)>,
+    previous: HashMap<(Entity, AnimationNodeIndex), (f32, u32)>,
+}
+
+// Events of a track passed when going from normalised time `from` to `to`, in playback order.
+// The span is unwrapped onto a continuous axis with one unit per cycle, so events in looped
+// cycles come out once per cycle. Going forwards the span includes its end, going backwards its
+// start, so an event on a boundary fires exactly once.
+fn passed_events(track: &ClipEventTrack, from: f32, to: f32, wraps: u32, reverse: bool) -> Vec<&ClipEventKey> {
+    let wraps = wraps as f32;
+    let (low, high) = if reverse { (to - wraps, from) } else { (from, to + wraps) };
+    let mut passed: Vec<(f32, &ClipEventKey)> = track
+        .events
+        .iter()
+        .flat_map(|key| (low.floor() as i32..=high.ceil() as i32).map(move |cycle| (cycle as f32 + key.time, key)))
+        .filter(|(time, _)| if reverse { *time >= low && *time < high } else { *time > low && *time <= high })
+        .collect();
+    passed.sort_by(|a, b| a.0.total_cmp(&b.0));
+    if reverse {
+        passed.reverse();
+    }
+    passed.into_iter().map(|(_, key)| key).collect()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn fire_clip_events(
+    mut commands: Commands,
+    mut events: EventWriter<ClipEvent>,
+    libraries: Res<ClipLibraries>,
+    clips: Res<Assets<AnimationClip>>,
+    graphs: Res<Assets<AnimationGraph>>,
+    mut owners: Query<(Entity, &ClipEvents, Option<&mut ClipEventState>)>,
+    scenes: Query<&GltfScene>,
+    parents: Query<&Parent>,
+    children: Query<&Children>,
+    players: Query<(&AnimationPlayer, &Handle<AnimationGraph>)>,
+) {
+    for (entity, declared, state) in owners.iter_mut() {
+        let Some(mut state) = state else {
+            commands.entity(entity).insert(ClipEventState::default());
+            continue;
+        };
+        // Clips are looked up again when a track names another clip, and until the library has
+        // all of them
+        if !state.clips.iter().map(|(name, _)| name).eq(declared.tracks.iter().map(|track| &track.clip)) {
+            let library = std::iter::once(entity)
+                .chain(parents.iter_ancestors(entity))
+                .find_map(|ancestor| scenes.get(ancestor).ok())
+                .and_then(|scene| libraries.for_scene(&scene.path));
+            state.clips = declared
+                .tracks
+                .iter()
+                .map(|track| Some((track.clip.clone(), library?.clip(&track.clip)?.clone())))
+                .collect::<Option<_>>()
+                .unwrap_or_default();
+        }
+
+        let mut previous = HashMap::default();
+        for player_entity in children.iter_descendants(entity) {
+            let Ok((player, graph)) = players.get(player_entity) else {
+                continue;
+            };
+            let Some(graph) = graphs.get(graph) else {
+                continue;
+            };
+            for (track, (_, handle)) in declared.tracks.iter().zip(&state.clips) {
+                let Some(duration) = clips.get(handle).map(AnimationClip::duration).filter(|duration| *duration > 0.0) else {
+                    continue;
+                };
+                let playing: Vec<(&AnimationNodeIndex, &ActiveAnimation)> = player
+                    .playing_animations()
+                    .filter(|(node, _)| graph.get(**node).is_some_and(|node| node.clip.as_ref() == Some(handle)))
+                    .collect();
+                for (node, animation) in &playing {
+                    previous.insert((player_entity, **node), (animation.seek_time(), animation.completions()));
+                }
+
+                let weight = |animation: &ActiveAnimation| {
+                    animation.path::<f32>("computed_weight").copied().unwrap_or(animation.weight())
+                };
+                let Some((node, animation)) = playing
+                    .into_iter()
+                    .filter(|(_, animation)| weight(animation) > 0.0)
+                    .max_by(|a, b| weight(a.1).total_cmp(&weight(b.1)))
+                else {
+                    continue;
+                };
+                // Clips that just started fire from their next frame on
+                let Some(&(from, completions)) = state.previous.get(&(player_entity, *node)) else {
+                    continue;
+                };
+                let to = animation.seek_time();
+                let wraps = animation.completions().saturating_sub(completions);
+                // Seeks, e.g. from phase locking, can move either way without wrapping
+                let reverse = if wraps > 0 { animation.speed() < 0.0 } else { to < from };
+                for key in passed_events(track, from / duration, to / duration, wraps, reverse) {
+                    events.send(ClipEvent { entity, clip: track.clip.clone(), payload: key.payload.clone() });
+                }
+            }
+        }
+        state.previous = previous;
+    }
+}
+
+pub struct EzClipEventPlugin;
+
+impl Plugin for EzClipEventPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<ClipEvents>()
+            .add_event::<ClipEvent>()
+            .add_systems(PostUpdate, fire_clip_events.in_set(PostAnimationSet::Pose));
+    }
+}
@@ -0,0 +1,118 @@
+//! Per-model libraries of the named animations in glTF files.
+//!
+//! Whenever a glTF finishes loading, every named animation in it is added to one
+//! `AnimationGraph` for that model, and the library maps the names to their graph nodes. All
+//! instances of the model share that graph, and gameplay plays clips by name, like
+//! `library.node("Armature|mixamo.com|Layer0")`, instead of by `#AnimationN` label. State
+//! machines and layers add nodes of their own to a copy of the graph, as they need one per use
+//! of a clip rather than one per clip.
+
+use bevy::gltf::Gltf;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+pub struct ClipLibrary {
+    pub graph: Handle<AnimationGraph>,
+    // Name, graph node and clip of every named animation, in the order of the file
+    clips: Vec<(String, AnimationNodeIndex, Handle<AnimationClip>)>,
+}
+
+// Lookups for gameplay code, which nothing in the demo does yet
+#[allow(dead_code)]
+impl ClipLibrary {
+    pub fn node(&self, name: &str) -> Option<AnimationNodeIndex> {
+        self.clips.iter().find(|(clip, _, _)| clip == name).map(|(_, node, _)| *node)
+    }
+
+    pub fn clip(&self, name: &str) -> Option<&Handle<AnimationClip>> {
+        self.clips.iter().find(|(clip, _, _)| clip == name).map(|(_, _, handle)| handle)
+    }
+
+    pub fn name(&self, clip: &Handle<AnimationClip>) -> Option<&str> {
+        self.clips.iter().find(|(_, _, handle)| handle == clip).map(|(name, _, _)| name.as_str())
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.clips.iter().map(|(name, _, _)| name.as_str())
+    }
+
+    // Node of the first animation in the file, for models that only have one
+    pub fn first(&self) -> Option<AnimationNodeIndex> {
+        self.clips.first().map(|(_, node, _)| *node)
+    }
+}
+
+impl ClipLibrary {
+    // Adds a clip from elsewhere, like one retargeted from another model, unless the library
+    // already has one by that name
+    pub(crate) fn add(&mut self, name: &str, clip: Handle<AnimationClip>, graphs: &mut Assets<AnimationGraph>) -> bool {
+        if self.clips.iter().any(|(existing, _, _)| existing == name) {
+            return false;
+        }
+        let Some(graph) = graphs.get_mut(&self.graph) else {
+            return false;
+        };
+        let node = graph.add_clip(clip.clone(), 1.0, graph.root);
+        self.clips.push((name.to_string(), node, clip));
+        true
+    }
+}
+
+// Clip libraries by the asset path of their glTF, like "scenes/Walker.glb"
+#[derive(Resource, Default)]
+pub struct ClipLibraries {
+    libraries: HashMap<String, ClipLibrary>,
+}
+
+impl ClipLibraries {
+    pub fn get(&self, model: &str) -> Option<&ClipLibrary> {
+        self.libraries.get(model)
+    }
+
+    pub(crate) fn get_mut(&mut self, model: &str) -> Option<&mut ClipLibrary> {
+        self.libraries.get_mut(model)
+    }
+
+    // Library of the glTF a scene path like "scenes/Walker.glb#Scene0" points into
+    pub fn for_scene(&self, scene: &str) -> Option<&ClipLibrary> {
+        self.get(scene.split('#').next().unwrap_or(scene))
+    }
+}
+
+pub(crate) fn build_clip_libraries(
+    mut events: EventReader<AssetEvent<Gltf>>,
+    asset_server: Res<AssetServer>,
+    gltfs: Res<Assets<Gltf>>,
+    mut graphs: ResMut<Assets<AnimationGraph>>,
+    mut libraries: ResMut<ClipLibraries>,
+) {
+    for event in events.read() {
+        let AssetEvent::LoadedWithDependencies { id } = event else {
+            continue;
+        };
+        let (Some(gltf), Some(path)) = (gltfs.get(*id), asset_server.get_path(*id)) else {
+            continue;
+        };
+
+        let mut graph = AnimationGraph::new();
+        let clips = gltf
+            .animations
+            .iter()
+            .filter_map(|clip| {
+                let (name, _) = gltf.named_animations.iter().find(|(_, named)| *named == clip)?;
+                Some((name.to_string(), graph.add_clip(clip.clone(), 1.0, graph.root), clip.clone()))
+            })
+            .collect();
+        let library = ClipLibrary { graph: graphs.add(graph), clips };
+        libraries.libraries.insert(path.without_label().to_string(), library);
+    }
+}
+
+pub struct EzClipLibraryPlugin;
+
+impl Plugin for EzClipLibraryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ClipLibraries>()
+            .add_systems(Update, build_clip_libraries);
+    }
+}
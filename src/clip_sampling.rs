@@ -0,0 +1,81 @@
+//! Sampling of skeletal clip curves at arbitrary times.
+//!
+//! Bevy only samples clips while applying them to their targets. Root motion, layering and
+//! retargeting need the value of a bone's curve at other times too, e.g. the start and end of
+//! a cycle, so this mirrors Bevy's keyframe interpolation for a single curve.
+
+use std::ops::{Add, Mul};
+use bevy::animation::{Interpolation, Keyframes, VariableCurve};
+use bevy::prelude::*;
+
+// Index of the segment containing `time` and the local factor within it. Times outside the
+// curve are clamped to its first or last keyframe.
+fn segment(timestamps: &[f32], time: f32) -> Option<(usize, usize, f32)> {
+    let last = timestamps.len().checked_sub(1)?;
+    if last == 0 || time <= timestamps[0] {
+        return Some((0, 0, 0.0));
+    }
+    if time >= timestamps[last] {
+        return Some((last, last, 0.0));
+    }
+    let next = timestamps.partition_point(|timestamp| *timestamp <= time);
+    let (start, end) = (timestamps[next - 1], timestamps[next]);
+    Some((next - 1, next, (time - start) / (end - start)))
+}
+
+fn interpolate<T>(
+    curve: &VariableCurve,
+    values: &[T],
+    time: f32,
+    lerp: impl Fn(T, T, f32) -> T,
+) -> Option<T>
+where
+    T: Copy + Mul<f32, Output = T> + Add<Output = T>,
+{
+    let (start, end, t) = segment(&curve.keyframe_timestamps, time)?;
+    match curve.interpolation {
+        Interpolation::Step => values.get(start).copied(),
+        Interpolation::Linear => Some(lerp(*values.get(start)?, *values.get(end)?, t)),
+        // Keyframes are stored as (in tangent, value, out tangent) triples
+        Interpolation::CubicSpline => {
+            let value_start = *values.get(start * 3 + 1)?;
+            if start == end {
+                return Some(value_start);
+            }
+            let tangent_out = *values.get(start * 3 + 2)?;
+            let tangent_in = *values.get(end * 3)?;
+            let value_end = *values.get(end * 3 + 1)?;
+            let duration = curve.keyframe_timestamps[end] - curve.keyframe_timestamps[start];
+            let (t2, t3) = (t * t, t * t * t);
+            Some(
+                value_start * (2.0 * t3 - 3.0 * t2 + 1.0)
+                    + tangent_out * (duration * (t3 - 2.0 * t2 + t))
+                    + value_end * (-2.0 * t3 + 3.0 * t2)
+                    + tangent_in * (duration * (t3 - t2)),
+            )
+        }
+    }
+}
+
+pub fn sample_translation(curves: &[VariableCurve], time: f32) -> Option<Vec3> {
+    curves.iter().find_map(|curve| match &curve.keyframes {
+        Keyframes::Translation(values) => interpolate(curve, values, time, Vec3::lerp),
+        _ => None,
+    })
+}
+
+pub fn sample_rotation(curves: &[VariableCurve], time: f32) -> Option<Quat> {
+    curves.iter().find_map(|curve| match &curve.keyframes {
+        Keyframes::Rotation(values) => {
+            interpolate(curve, values, time, Quat::slerp).map(Quat::normalize)
+        }
+        _ => None,
+    })
+}
+
+pub fn sample_scale(curves: &[VariableCurve], time: f32) -> Option<Vec3> {
+    curves.iter().find_map(|curve| match &curve.keyframes {
+        Keyframes::Scale(values) => interpolate(curve, values, time, Vec3::lerp),
+        _ => None,
+    })
+}
@@ -0,0 +1,284 @@
+//! Multi-channel clips: independent keyframe tracks per transform and colour channel.
+
+use bevy::math::VectorSpace;
+use bevy::prelude::*;
+use crate::GameState;
+use crate::interpolators::InterpolationFactor;
+use crate::quat_spline::squad_segment;
+use crate::timeline::Timeline;
+
+// How a track moves from one key to the next
+#[derive(Reflect, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum KeyInterpolation {
+    // Hold the previous key until the next one is reached
+    Step,
+    #[default]
+    Linear,
+    // Catmull-Rom style cubic through the keys, with tangents from the neighbouring keys.
+    // Rotation tracks use SQUAD instead, which stays on the unit sphere.
+    Cubic,
+}
+
+#[derive(Reflect, Clone, Copy, Debug)]
+pub struct Keyframe<T> {
+    pub time: f32,
+    pub value: T,
+}
+
+// A single channel: a list of keys sorted by time and how to interpolate between them
+#[derive(Reflect, Clone, Debug)]
+pub struct Track<T> {
+    pub interpolation: KeyInterpolation,
+    pub keys: Vec<Keyframe<T>>,
+}
+
+impl<T> Track<T> {
+    #[allow(dead_code)]
+    pub fn new(interpolation: KeyInterpolation, keys: impl IntoIterator<Item = (f32, T)>) -> Self {
+        let mut keys: Vec<Keyframe<T>> = keys
+            .into_iter()
+            .map(|(time, value)| Keyframe { time, value })
+            .collect();
+        keys.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Track { interpolation, keys }
+    }
+
+    // Finds the segment containing `time`, returning the index of its first key and the local
+    // factor within it. Times outside the track are clamped to the first or last key.
+    pub(crate) fn segment(&self, time: f32) -> Option<(usize, f32)> {
+        let last = self.keys.len().checked_sub(1)?;
+        if last == 0 || time <= self.keys[0].time {
+            return Some((0, 0.0));
+        }
+        if time >= self.keys[last].time {
+            return Some((last - 1, 1.0));
+        }
+        let next = self.keys.partition_point(|key| key.time <= time);
+        let (start, end) = (self.keys[next - 1].time, self.keys[next].time);
+        Some((next - 1, (time - start) / (end - start)))
+    }
+}
+
+impl<T: VectorSpace> Track<T> {
+    pub fn sample(&self, time: f32) -> Option<T> {
+        let (index, t) = self.segment(time)?;
+        let Some(next) = self.keys.get(index + 1) else {
+            return Some(self.keys[index].value);
+        };
+        let current = &self.keys[index];
+
+        Some(match self.interpolation {
+            KeyInterpolation::Step if t < 1.0 => current.value,
+            KeyInterpolation::Step => next.value,
+            KeyInterpolation::Linear => current.value.lerp(next.value, t),
+            KeyInterpolation::Cubic => {
+                let duration = next.time - current.time;
+                let m0 = self.tangent(index) * duration;
+                let m1 = self.tangent(index + 1) * duration;
+                hermite(current.value, m0, next.value, m1, t)
+            }
+        })
+    }
+
+    // Finite-difference tangent at a key, in value per unit of time
+    fn tangent(&self, index: usize) -> T {
+        let before = &self.keys[index.saturating_sub(1)];
+        let after = &self.keys[(index + 1).min(self.keys.len() - 1)];
+        let span = after.time - before.time;
+        if span <= f32::EPSILON {
+            T::ZERO
+        } else {
+            (after.value - before.value) / span
+        }
+    }
+}
+
+impl Track<Quat> {
+    // Rotations stay on the unit sphere: linear tracks slerp, cubic tracks use SQUAD
+    pub fn sample_rotation(&self, time: f32) -> Option<Quat> {
+        let (index, t) = self.segment(time)?;
+        let Some(next) = self.keys.get(index + 1) else {
+            return Some(self.keys[index].value);
+        };
+        let current = &self.keys[index];
+        let key = |key: &Keyframe<Quat>| (key.time, key.value);
+
+        Some(match self.interpolation {
+            KeyInterpolation::Step if t < 1.0 => current.value,
+            KeyInterpolation::Step => next.value,
+            KeyInterpolation::Linear => current.value.slerp(next.value, t),
+            KeyInterpolation::Cubic => squad_segment(
+                index.checked_sub(1).map(|i| key(&self.keys[i])),
+                key(current),
+                key(next),
+                self.keys.get(index + 2).map(key),
+                t,
+            ),
+        })
+    }
+}
+
+fn hermite<T: VectorSpace>(p0: T, m0: T, p1: T, m1: T, t: f32) -> T {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    p0 * (2.0 * t3 - 3.0 * t2 + 1.0)
+        + m0 * (t3 - 2.0 * t2 + t)
+        + p1 * (-2.0 * t3 + 3.0 * t2)
+        + m1 * (t3 - t2)
+}
+
+// A clip groups independent tracks for one entity. Channels without a track are left untouched.
+// Key times run from 0 to `duration`, which is mapped onto the shared interpolation factor.
+#[derive(Reflect, Component, Clone, Default, Debug)]
+#[reflect(Component)]
+pub struct ClipComponent {
+    pub duration: f32,
+    pub translation_x: Option<Track<f32>>,
+    pub translation_y: Option<Track<f32>>,
+    pub translation_z: Option<Track<f32>>,
+    pub rotation: Option<Track<Quat>>,
+    pub scale: Option<Track<Vec3>>,
+    pub color: Option<Track<Oklaba>>,
+    pub(crate) time: f32,
+}
+
+// Builder used when spawning clips from code
+#[allow(dead_code)]
+impl ClipComponent {
+    pub fn new(duration: f32) -> Self {
+        ClipComponent {
+            duration,
+            ..default()
+        }
+    }
+
+    pub fn with_translation_x(mut self, track: Track<f32>) -> Self {
+        self.translation_x = Some(track);
+        self
+    }
+
+    pub fn with_translation_y(mut self, track: Track<f32>) -> Self {
+        self.translation_y = Some(track);
+        self
+    }
+
+    pub fn with_translation_z(mut self, track: Track<f32>) -> Self {
+        self.translation_z = Some(track);
+        self
+    }
+
+    pub fn with_rotation(mut self, track: Track<Quat>) -> Self {
+        self.rotation = Some(track);
+        self
+    }
+
+    pub fn with_scale(mut self, track: Track<Vec3>) -> Self {
+        self.scale = Some(track);
+        self
+    }
+
+    pub fn with_color(mut self, track: Track<Oklaba>) -> Self {
+        self.color = Some(track);
+        self
+    }
+
+    // Writes every transform channel that has a track at the given clip time
+    pub fn apply_transform(&self, time: f32, transform: &mut Transform) {
+        let translation = &mut transform.translation;
+        for (track, axis) in [
+            (&self.translation_x, &mut translation.x),
+            (&self.translation_y, &mut translation.y),
+            (&self.translation_z, &mut translation.z),
+        ] {
+            if let Some(value) = track.as_ref().and_then(|track| track.sample(time)) {
+                *axis = value;
+            }
+        }
+        if let Some(rotation) = self.rotation.as_ref().and_then(|track| track.sample_rotation(time)) {
+            transform.rotation = rotation;
+        }
+        if let Some(scale) = self.scale.as_ref().and_then(|track| track.sample(time)) {
+            transform.scale = scale;
+        }
+    }
+
+    pub fn sample_color(&self, time: f32) -> Option<Oklaba> {
+        self.color.as_ref().and_then(|track| track.sample(time))
+    }
+}
+
+fn clip_time_system(
+    mut query: Query<(&mut ClipComponent, Option<&Timeline>)>,
+    interpolation_factor: Res<InterpolationFactor>,
+) {
+    for (mut clip, timeline) in query.iter_mut() {
+        clip.time = timeline.map_or(interpolation_factor.0, Timeline::value) * clip.duration;
+    }
+}
+
+fn update_clip_transform_system(
+    mut query: Query<(&mut Transform, &ClipComponent)>,
+) {
+    for (mut transform, clip) in query.iter_mut() {
+        clip.apply_transform(clip.time, &mut transform);
+    }
+}
+
+fn update_clip_color_system(
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    query: Query<(&ClipComponent, &Handle<StandardMaterial>)>,
+) {
+    for (clip, material_handle) in query.iter() {
+        let Some(color) = clip.sample_color(clip.time) else {
+            continue;
+        };
+        if let Some(material) = materials.get_mut(material_handle) {
+            material.base_color = color.into();
+        }
+    }
+}
+
+// EXAMPLE
+fn _spawn_clip_cube_system(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    let clip = ClipComponent::new(2.0)
+        .with_translation_x(Track::new(KeyInterpolation::Cubic, [(0.0, -1.0), (1.0, 0.5), (2.0, 1.0)]))
+        .with_translation_y(Track::new(KeyInterpolation::Step, [(0.0, 0.0), (0.5, 0.5), (1.5, 0.0)]))
+        .with_translation_z(Track::new(KeyInterpolation::Linear, [(0.0, -3.0)]))
+        .with_rotation(Track::new(
+            KeyInterpolation::Linear,
+            [(0.0, Quat::IDENTITY), (2.0, Quat::from_rotation_y(std::f32::consts::PI))],
+        ))
+        .with_scale(Track::new(KeyInterpolation::Linear, [(0.5, Vec3::ONE), (1.0, Vec3::splat(1.5))]))
+        .with_color(Track::new(
+            KeyInterpolation::Cubic,
+            [
+                (0.0, Oklaba::new(0.7, -0.1, -0.1, 1.0)),
+                (1.0, Oklaba::new(0.7, 0.1, 0.1, 1.0)),
+                (2.0, Oklaba::new(0.7, -0.1, 0.1, 1.0)),
+            ],
+        ));
+
+    commands.spawn(PbrBundle {
+        mesh: meshes.add(Cuboid::new(1.0, 1.0, 1.0)),
+        material: materials.add(StandardMaterial::default()),
+        ..Default::default()
+    })
+        .insert(clip);
+}
+
+pub struct EzClipPlugin;
+
+impl Plugin for EzClipPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<ClipComponent>()
+            .add_systems(Update,
+                         (
+                             clip_time_system,
+                             (update_clip_transform_system, update_clip_color_system),
+                         ).chain().run_if(in_state(GameState::Playing)));
+    }
+}
@@ -0,0 +1,195 @@
+//! Constraint stacks: rules that adjust an entity's transform after it has been animated.
+//!
+//! Each entity has one stack, evaluated in `order` after animation players have sampled their
+//! clips and poses have been blended in, and before inverse kinematics, so constraints see and
+//! adjust both interpolated and skeletal animation. Every constraint blends its result over
+//! what the constraints before it produced by its weight.
+//! Copy and child-of constraints work in world space, built from the current local transforms,
+//! so targets moved earlier in the frame are seen where they are now.
+
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use crate::GameState;
+use crate::ik::world_transform;
+use crate::animator::PostAnimationSet;
+use crate::written_transform::WrittenTransform;
+
+#[derive(Reflect, Clone, Debug)]
+pub enum ConstraintTarget {
+    Entity(Entity),
+    // First entity with this `Name`, which scene files can refer to
+    Named(String),
+}
+
+impl Default for ConstraintTarget {
+    fn default() -> Self {
+        ConstraintTarget::Named(String::new())
+    }
+}
+
+impl ConstraintTarget {
+    fn entity(&self, named: &Query<(Entity, &Name)>) -> Option<Entity> {
+        match self {
+            ConstraintTarget::Entity(entity) => Some(*entity),
+            ConstraintTarget::Named(name) => named.iter().find(|(_, target)| target.as_str() == name).map(|(entity, _)| entity),
+        }
+    }
+}
+
+#[derive(Reflect, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum TransformChannel {
+    #[default]
+    Location,
+    Rotation,
+    Scale,
+}
+
+#[derive(Reflect, Clone, Debug)]
+pub enum ConstraintKind {
+    // Takes one channel of the target's world transform
+    Copy { target: ConstraintTarget, channel: TransformChannel },
+    // Moves with `parents[active]` as if it were a child of it, from where it was when that
+    // parent became active, so switching parents doesn't make the entity jump
+    ChildOf { parents: Vec<ConstraintTarget>, active: usize },
+    // Keeps the local value of a channel between `min` and `max`. Rotations are limited as
+    // Euler angles around X, Y and Z in radians, applied in YXZ order like `LookAt` limits:
+    // a turn around Y, then a tilt around the turned X, then a roll around Z.
+    Limit { channel: TransformChannel, min: Vec3, max: Vec3 },
+}
+
+impl Default for ConstraintKind {
+    fn default() -> Self {
+        ConstraintKind::Copy { target: ConstraintTarget::default(), channel: TransformChannel::default() }
+    }
+}
+
+#[derive(Reflect, Clone, Debug)]
+pub struct Constraint {
+    pub kind: ConstraintKind,
+    pub weight: f32,
+    // Constraints run from the lowest order up, in list order when equal
+    pub order: i32,
+}
+
+impl Default for Constraint {
+    fn default() -> Self {
+        Constraint { kind: ConstraintKind::default(), weight: 1.0, order: 0 }
+    }
+}
+
+#[derive(Component, Reflect, Clone, Default, Debug)]
+#[reflect(Component)]
+pub struct ConstraintStack {
+    pub constraints: Vec<Constraint>,
+}
+
+// For each child-of constraint by index, its active parent and the offset from that parent
+// which kept the entity in place when the parent became active, and what the stack wrote
+#[derive(Component, Default)]
+struct ConstraintState {
+    parented: HashMap<usize, (Entity, GlobalTransform)>,
+    written: WrittenTransform,
+}
+
+// Blends `to` over `from` by `weight`, channel by channel
+fn blend(from: Transform, to: Transform, weight: f32) -> Transform {
+    Transform {
+        translation: from.translation.lerp(to.translation, weight),
+        rotation: from.rotation.slerp(to.rotation, weight).normalize(),
+        scale: from.scale.lerp(to.scale, weight),
+    }
+}
+
+fn limit(mut transform: Transform, channel: TransformChannel, min: Vec3, max: Vec3) -> Transform {
+    match channel {
+        TransformChannel::Location => transform.translation = transform.translation.clamp(min, max),
+        TransformChannel::Rotation => {
+            let (y, x, z) = transform.rotation.to_euler(EulerRot::YXZ);
+            let angles = Vec3::new(x, y, z).clamp(min, max);
+            transform.rotation = Quat::from_euler(EulerRot::YXZ, angles.y, angles.x, angles.z);
+        }
+        TransformChannel::Scale => transform.scale = transform.scale.clamp(min, max),
+    }
+    transform
+}
+
+fn evaluate_constraints(
+    mut commands: Commands,
+    mut stacks: Query<(Entity, &ConstraintStack, Option<&mut ConstraintState>)>,
+    named: Query<(Entity, &Name)>,
+    parents: Query<&Parent>,
+    mut transforms: Query<&mut Transform>,
+) {
+    for (entity, stack, state) in stacks.iter_mut() {
+        let Some(mut state) = state else {
+            commands.entity(entity).insert(ConstraintState::default());
+            continue;
+        };
+        let Ok(current) = transforms.get(entity).copied() else {
+            continue;
+        };
+        let input = state.written.input(current);
+        let mut local = input;
+        let parent = parents.get(entity).map_or(GlobalTransform::IDENTITY, |parent| world_transform(parent.get(), &parents, &transforms));
+
+        let mut order: Vec<(usize, &Constraint)> = stack.constraints.iter().enumerate().collect();
+        order.sort_by_key(|(_, constraint)| constraint.order);
+        for (index, constraint) in order {
+            let weight = constraint.weight.clamp(0.0, 1.0);
+            let constrained = match &constraint.kind {
+                ConstraintKind::Copy { target, channel } => {
+                    let Some(target) = target.entity(&named).filter(|target| *target != entity) else {
+                        continue;
+                    };
+                    let copied = world_transform(target, &parents, &transforms).reparented_to(&parent);
+                    match channel {
+                        TransformChannel::Location => local.with_translation(copied.translation),
+                        TransformChannel::Rotation => local.with_rotation(copied.rotation),
+                        TransformChannel::Scale => local.with_scale(copied.scale),
+                    }
+                }
+                ConstraintKind::ChildOf { parents: targets, active } => {
+                    let Some(target) = targets.get(*active).and_then(|target| target.entity(&named)).filter(|target| *target != entity) else {
+                        state.parented.remove(&index);
+                        continue;
+                    };
+                    let target_world = world_transform(target, &parents, &transforms);
+                    let own_world = parent * local;
+                    let offset = match state.parented.get(&index).copied() {
+                        Some((current, offset)) if current == target => offset,
+                        previous => {
+                            // Start from where the previous parent held the entity, or where it is
+                            let held = previous.map_or(own_world, |(previous, offset)| {
+                                world_transform(previous, &parents, &transforms) * offset * own_world
+                            });
+                            let offset = GlobalTransform::from(target_world.affine().inverse())
+                                * held
+                                * GlobalTransform::from(own_world.affine().inverse());
+                            state.parented.insert(index, (target, offset));
+                            offset
+                        }
+                    };
+                    (target_world * offset * own_world).reparented_to(&parent)
+                }
+                ConstraintKind::Limit { channel, min, max } => limit(local, *channel, *min, *max),
+            };
+            local = blend(local, constrained, weight);
+        }
+
+        if let Ok(mut transform) = transforms.get_mut(entity) {
+            *transform = local;
+        }
+        state.written.write(input, local);
+    }
+}
+
+pub struct EzConstraintPlugin;
+
+impl Plugin for EzConstraintPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<ConstraintStack>()
+            .add_systems(PostUpdate, evaluate_constraints
+                .in_set(PostAnimationSet::Constraints)
+                .run_if(in_state(GameState::Playing)));
+    }
+}
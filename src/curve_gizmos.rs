@@ -0,0 +1,187 @@
+//! Debug drawing of interpolation and keyframe paths.
+
+use bevy::color::palettes::css::{AQUA, ORANGE, WHITE, YELLOW};
+use bevy::prelude::*;
+use crate::GameState;
+use crate::interpolators::InterpolatingComponent;
+use crate::keyframes::KeyframingComponent;
+use crate::vstransform::VSTransform;
+
+// Global settings for the path gizmos, toggled as a whole with `G`
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct CurveGizmoSettings {
+    pub enabled: bool,
+    // Number of line segments used to draw each path
+    pub samples: usize,
+    pub key_radius: f32,
+    // Length of the drawn tangents relative to the path velocity
+    pub tangent_scale: f32,
+    pub path_color: Color,
+    pub key_color: Color,
+    pub tangent_color: Color,
+    pub marker_color: Color,
+}
+
+impl Default for CurveGizmoSettings {
+    fn default() -> Self {
+        CurveGizmoSettings {
+            enabled: cfg!(debug_assertions),
+            samples: 64,
+            key_radius: 0.05,
+            tangent_scale: 0.25,
+            path_color: WHITE.into(),
+            key_color: AQUA.into(),
+            tangent_color: ORANGE.into(),
+            marker_color: YELLOW.into(),
+        }
+    }
+}
+
+// Per-entity toggle, added automatically to every animated transform
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct CurveGizmo {
+    pub visible: bool,
+}
+
+impl Default for CurveGizmo {
+    fn default() -> Self {
+        CurveGizmo { visible: true }
+    }
+}
+
+// Step used to estimate tangents from neighbouring samples
+const TANGENT_EPSILON: f32 = 1e-3;
+
+fn insert_curve_gizmos(
+    mut commands: Commands,
+    query: Query<
+        Entity,
+        (
+            Or<(
+                Added<InterpolatingComponent<VSTransform>>,
+                Added<KeyframingComponent<VSTransform>>,
+            )>,
+            Without<CurveGizmo>,
+        ),
+    >,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).insert(CurveGizmo::default());
+    }
+}
+
+fn toggle_curve_gizmos(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<CurveGizmoSettings>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyG) {
+        settings.enabled = !settings.enabled;
+    }
+}
+
+// Draws one path given a sampler over normalised t, the key times and the current value.
+// Paths are sampled in the entity's local space, so they are moved into the parent's space.
+fn draw_path(
+    gizmos: &mut Gizmos,
+    settings: &CurveGizmoSettings,
+    parent: Option<&GlobalTransform>,
+    sample: impl Fn(f32) -> Vec3,
+    key_times: impl Iterator<Item = f32>,
+    current: Vec3,
+) {
+    let to_world = |point: Vec3| match parent {
+        Some(parent) => parent.transform_point(point),
+        None => point,
+    };
+
+    let samples = settings.samples.max(1);
+    gizmos.linestrip(
+        (0..=samples).map(|i| to_world(sample(i as f32 / samples as f32))),
+        settings.path_color,
+    );
+
+    for t in key_times {
+        let key = to_world(sample(t));
+        let before = to_world(sample((t - TANGENT_EPSILON).max(0.0)));
+        let after = to_world(sample((t + TANGENT_EPSILON).min(1.0)));
+        let span = (t + TANGENT_EPSILON).min(1.0) - (t - TANGENT_EPSILON).max(0.0);
+        let tangent = (after - before) / span * settings.tangent_scale;
+
+        gizmos.sphere(key, Quat::IDENTITY, settings.key_radius, settings.key_color);
+        gizmos.line(key - tangent, key + tangent, settings.tangent_color);
+    }
+
+    gizmos.sphere(
+        to_world(current),
+        Quat::IDENTITY,
+        settings.key_radius * 1.5,
+        settings.marker_color,
+    );
+}
+
+fn draw_interpolation_paths(
+    mut gizmos: Gizmos,
+    settings: Res<CurveGizmoSettings>,
+    query: Query<(&InterpolatingComponent<VSTransform>, &CurveGizmo, Option<&Parent>)>,
+    parents: Query<&GlobalTransform>,
+) {
+    if !settings.enabled {
+        return;
+    }
+    for (component, gizmo, parent) in query.iter() {
+        if !gizmo.visible {
+            continue;
+        }
+        draw_path(
+            &mut gizmos,
+            &settings,
+            parent.and_then(|parent| parents.get(parent.get()).ok()),
+            |t| component.sample(t).transform().translation,
+            [0.0, 1.0].into_iter(),
+            component.current.transform().translation,
+        );
+    }
+}
+
+fn draw_keyframe_paths(
+    mut gizmos: Gizmos,
+    settings: Res<CurveGizmoSettings>,
+    query: Query<(&KeyframingComponent<VSTransform>, &CurveGizmo, Option<&Parent>)>,
+    parents: Query<&GlobalTransform>,
+) {
+    if !settings.enabled {
+        return;
+    }
+    for (component, gizmo, parent) in query.iter() {
+        if !gizmo.visible {
+            continue;
+        }
+        draw_path(
+            &mut gizmos,
+            &settings,
+            parent.and_then(|parent| parents.get(parent.get()).ok()),
+            |t| component.sample(t).transform().translation,
+            component.curve.key_times(),
+            component.current.transform().translation,
+        );
+    }
+}
+
+pub struct EzCurveGizmoPlugin;
+
+impl Plugin for EzCurveGizmoPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CurveGizmoSettings>()
+            .register_type::<CurveGizmoSettings>()
+            .register_type::<CurveGizmo>()
+            .add_systems(Update,
+                         (
+                             insert_curve_gizmos,
+                             toggle_curve_gizmos,
+                             draw_interpolation_paths,
+                             draw_keyframe_paths,
+                         ).run_if(in_state(GameState::Playing)));
+    }
+}
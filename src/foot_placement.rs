@@ -0,0 +1,142 @@
+//! Foot placement: keeping animated feet on uneven ground.
+//!
+//! Clips are authored on flat ground at the height of the model's origin. After they are
+//! sampled, a ray is cast down at each foot against the `GroundCollider`s, and the foot is moved
+//! by how far the ground there is above or below that height, keeping the lift the clip gives
+//! it. The pelvis is lowered so the leg over the lowest ground can still reach it, and the leg
+//! chains are bent with the two-bone solver. Offsets are smoothed so feet don't pop at ledges.
+
+use bevy::prelude::*;
+use crate::animator::PostAnimationSet;
+use crate::ground::{raycast_ground, GroundCollider};
+use crate::ik::{find_bone, rotate_in_world, translate_in_world, world_transform, BoneChain};
+
+// Bone names of a leg from the hip down, like "mixamorig:LeftUpLeg", "mixamorig:LeftLeg" and
+// "mixamorig:LeftFoot"
+#[derive(Reflect, Clone, Default, Debug)]
+pub struct LegBones {
+    pub thigh: String,
+    pub shin: String,
+    pub foot: String,
+}
+
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct FootPlacement {
+    pub pelvis: String,
+    pub legs: Vec<LegBones>,
+    // Height above the model's origin that rays start from, so feet find ground above it
+    pub ray_height: f32,
+    // How far below the model's origin rays reach
+    pub ray_depth: f32,
+    // Furthest the pelvis is lowered for a foot to reach lower ground
+    pub max_pelvis_drop: f32,
+    // How much feet tilt to match the slope under them
+    pub alignment: f32,
+    pub weight: f32,
+    // Seconds for offsets to close most of the gap to the ground, zero to follow it at once
+    pub smoothing: f32,
+}
+
+impl Default for FootPlacement {
+    fn default() -> Self {
+        let leg = |side: &str| LegBones {
+            thigh: format!("mixamorig:{side}UpLeg"),
+            shin: format!("mixamorig:{side}Leg"),
+            foot: format!("mixamorig:{side}Foot"),
+        };
+        FootPlacement {
+            pelvis: "mixamorig:Hips".to_string(),
+            legs: vec![leg("Left"), leg("Right")],
+            ray_height: 0.5,
+            ray_depth: 0.5,
+            max_pelvis_drop: 0.4,
+            alignment: 1.0,
+            weight: 1.0,
+            smoothing: 0.1,
+        }
+    }
+}
+
+// Bones found for a `FootPlacement` and its smoothed offsets
+#[derive(Component, Default)]
+struct FootPlacementState {
+    pelvis: Option<Entity>,
+    legs: Vec<BoneChain>,
+    pelvis_offset: f32,
+    foot_offsets: Vec<f32>,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn place_feet(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut models: Query<(Entity, &FootPlacement, Option<&mut FootPlacementState>)>,
+    colliders: Query<(&GroundCollider, &GlobalTransform)>,
+    children: Query<&Children>,
+    names: Query<&Name>,
+    parents: Query<&Parent>,
+    mut transforms: Query<&mut Transform>,
+) {
+    for (model, placement, state) in models.iter_mut() {
+        let Some(mut state) = state else {
+            commands.entity(model).insert(FootPlacementState::default());
+            continue;
+        };
+        if state.pelvis.is_none() || state.legs.len() != placement.legs.len() {
+            state.pelvis = find_bone(model, &placement.pelvis, &children, &names);
+            state.legs = placement
+                .legs
+                .iter()
+                .filter_map(|leg| BoneChain::find(model, [&leg.thigh, &leg.shin, &leg.foot], &children, &names))
+                .collect();
+            state.foot_offsets = vec![0.0; state.legs.len()];
+        }
+        let Some(pelvis) = state.pelvis else {
+            continue;
+        };
+
+        let delta = time.delta_seconds();
+        let blend = if placement.smoothing > 0.0 { 1.0 - (-delta / placement.smoothing).exp() } else { 1.0 };
+        let ground = world_transform(model, &parents, &transforms).translation().y;
+
+        // Ground under each foot, relative to the flat ground the clip assumes
+        let feet: Vec<Vec3> = state
+            .legs
+            .iter()
+            .map(|leg| world_transform(leg.end, &parents, &transforms).translation())
+            .collect();
+        let mut normals = Vec::with_capacity(feet.len());
+        for (index, foot) in feet.iter().enumerate() {
+            let origin = Vec3::new(foot.x, ground + placement.ray_height, foot.z);
+            let hit = raycast_ground(colliders.iter(), origin, placement.ray_height + placement.ray_depth);
+            let target = hit.map_or(0.0, |hit| hit.point.y - ground);
+            state.foot_offsets[index] += (target - state.foot_offsets[index]) * blend;
+            normals.push(hit.map(|hit| hit.normal));
+        }
+
+        let lowest = state.foot_offsets.iter().copied().fold(f32::INFINITY, f32::min);
+        let pelvis_target = if lowest.is_finite() { lowest.min(0.0).max(-placement.max_pelvis_drop) } else { 0.0 };
+        state.pelvis_offset += (pelvis_target - state.pelvis_offset) * blend;
+
+        let weight = placement.weight.clamp(0.0, 1.0);
+        translate_in_world(pelvis, Vec3::Y * state.pelvis_offset * weight, &parents, &mut transforms);
+        for ((leg, foot), (offset, normal)) in state.legs.iter().zip(&feet).zip(state.foot_offsets.iter().zip(normals)) {
+            let target = *foot + Vec3::Y * *offset;
+            leg.solve(target, None, weight, &parents, &mut transforms);
+            if let Some(normal) = normal.filter(|_| placement.alignment > 0.0) {
+                let tilt = Quat::IDENTITY.slerp(Quat::from_rotation_arc(Vec3::Y, normal), placement.alignment.min(1.0) * weight);
+                rotate_in_world(leg.end, tilt, &parents, &mut transforms);
+            }
+        }
+    }
+}
+
+pub struct EzFootPlacementPlugin;
+
+impl Plugin for EzFootPlacementPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<FootPlacement>()
+            .add_systems(PostUpdate, place_feet.in_set(PostAnimationSet::Ik));
+    }
+}
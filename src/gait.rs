@@ -0,0 +1,319 @@
+//! Procedural gait: walk cycles made from a few parameters instead of a hand-animated clip.
+//!
+//! From a stride length, cadence, step height, hip sway and duty factor, `GaitParameters`
+//! builds keyframe tracks over one cycle: where the hips and each foot are in the model's space,
+//! with the model facing +Z. Each foot slides back along the ground while it is planted, for the
+//! duty factor's share of the cycle, and arcs forward while it swings. The hips sway towards
+//! the first leg while it is planted and drop so the planted legs can still reach their feet.
+//! The tracks are measured against the rig's standing pose, taken when the generator starts,
+//! and applied each frame by moving the hips and bending the legs with the two-bone solver, so
+//! any rig with legs of three bones can walk. Feet stay planted when the model moves forward
+//! at `GaitParameters::speed`, which a `ProceduralGait` that `travels` does by itself.
+//!
+//! Tracks are Catmull-Rom splines with a key past each end of the cycle, taken from the cycles
+//! before and after, so they run smoothly across the seam where the cycle starts again.
+
+use std::f32::consts::{PI, TAU};
+use bevy::prelude::*;
+use crate::GameState;
+use crate::animator::PostAnimationSet;
+use crate::foot_placement::LegBones;
+use crate::ik::{find_bone, rotate_in_world, translate_in_world, world_transform, BoneChain};
+use crate::keyframes::{Curve, KeyframeValue};
+use crate::vstransform::VSTransform;
+
+// Keys made for each track over one cycle
+const KEYS_PER_CYCLE: usize = 32;
+// Tension of the track splines, which makes them Catmull-Rom splines
+const TENSION: f32 = 0.5;
+// Share of a leg's length it stretches to at most, keeping knees a little bent
+const REACH: f32 = 0.98;
+
+#[derive(Reflect, Clone, Copy, PartialEq, Debug)]
+pub struct GaitParameters {
+    // Ground covered in one cycle, in which every leg takes one step
+    pub stride_length: f32,
+    // Steps per minute, with two steps to a cycle as for a pair of legs
+    pub cadence: f32,
+    // Height feet are lifted to halfway through their swing
+    pub step_height: f32,
+    // How far the hips move to either side
+    pub hip_sway: f32,
+    // Share of the cycle each foot is planted, over one half for a walk
+    pub duty_factor: f32,
+}
+
+impl Default for GaitParameters {
+    fn default() -> Self {
+        GaitParameters {
+            stride_length: 1.4,
+            cadence: 110.0,
+            step_height: 0.12,
+            hip_sway: 0.03,
+            duty_factor: 0.6,
+        }
+    }
+}
+
+// Measurements of a rig in its standing pose, in the model's space
+pub struct GaitRig {
+    pub hips: Vec3,
+    pub feet: Vec<Vec3>,
+    // Length of the shortest leg from hip to ankle
+    pub leg_length: f32,
+}
+
+// Positions of the hips and of each foot over one cycle, in the model's space, with one more key
+// on either side from the neighbouring cycles
+pub struct GaitTracks {
+    pub hips: Curve<VSTransform>,
+    pub feet: Vec<Curve<VSTransform>>,
+}
+
+impl GaitParameters {
+    pub fn cycles_per_second(&self) -> f32 {
+        self.cadence / 120.0
+    }
+
+    // Speed the model moves at for planted feet not to slide
+    pub fn speed(&self) -> f32 {
+        self.stride_length * self.cycles_per_second()
+    }
+
+    // How far a foot is ahead of and above its standing position at `phase` of its own cycle,
+    // which starts as it is planted. While planted it moves back as fast as the model moves
+    // forward, so it covers the stride times the duty factor, and swings forward as far.
+    fn foot_offset(&self, phase: f32) -> Vec2 {
+        let duty = self.duty_factor.clamp(0.05, 0.95);
+        let reach = self.stride_length * duty;
+        if phase < duty {
+            return Vec2::new(reach * (0.5 - phase / duty), 0.0);
+        }
+        let swing = (phase - duty) / (1.0 - duty);
+        let eased = swing * swing * (3.0 - 2.0 * swing);
+        Vec2::new(reach * (eased - 0.5), self.step_height * (PI * swing).sin())
+    }
+
+    // Tracks for `rig` with its legs starting their cycles at `phases`
+    pub fn tracks(&self, rig: &GaitRig, phases: &[f32]) -> GaitTracks {
+        let duty = self.duty_factor.clamp(0.05, 0.95);
+        let keys = KEYS_PER_CYCLE as i32;
+        let times = (-1..=keys + 1).map(|key| key as f32 / KEYS_PER_CYCLE as f32);
+        let feet_at = |time: f32| -> Vec<(Vec3, bool)> {
+            rig.feet
+                .iter()
+                .zip(phases)
+                .map(|(home, phase)| {
+                    let phase = (time - phase).rem_euclid(1.0);
+                    let offset = self.foot_offset(phase);
+                    (*home + Vec3::new(0.0, offset.y, offset.x), phase < duty)
+                })
+                .collect()
+        };
+        let side = rig.feet.first().map_or(0.0, |foot| (foot.x - rig.hips.x).signum());
+        let first_phase = phases.first().copied().unwrap_or(0.0);
+
+        let hips = times
+            .clone()
+            .map(|time| {
+                let sway = self.hip_sway * side * (TAU * (time - first_phase - duty * 0.5)).cos();
+                let mut hips = rig.hips + Vec3::X * sway;
+                // Low enough for every planted foot to be within reach
+                let reach = rig.leg_length * REACH;
+                for (foot, _) in feet_at(time).into_iter().filter(|(_, planted)| *planted) {
+                    let across = (foot - hips).xz().length_squared();
+                    hips.y = hips.y.min(foot.y + (reach * reach - across).max(0.0).sqrt());
+                }
+                VSTransform::from(Transform::from_translation(hips))
+            })
+            .collect();
+        let feet = (0..rig.feet.len())
+            .map(|foot| {
+                let keys = times.clone().map(|time| VSTransform::from(Transform::from_translation(feet_at(time)[foot].0)));
+                Curve::new(TENSION, keys.collect())
+            })
+            .collect();
+        GaitTracks { hips: Curve::new(TENSION, hips), feet }
+    }
+}
+
+impl GaitTracks {
+    // Samples a track at `phase` of the cycle, skipping the segments to the keys outside it
+    fn sample(track: &Curve<VSTransform>, phase: f32) -> VSTransform {
+        let segments = (KEYS_PER_CYCLE + 2) as f32;
+        VSTransform::sample_keyframes(track, (phase * KEYS_PER_CYCLE as f32 + 1.0) / segments)
+    }
+}
+
+#[derive(Reflect, Clone, Default, Debug)]
+pub struct GaitLeg {
+    pub bones: LegBones,
+    // Point of the cycle at which this foot is planted
+    pub phase: f32,
+}
+
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct ProceduralGait {
+    pub parameters: GaitParameters,
+    pub pelvis: String,
+    pub legs: Vec<GaitLeg>,
+    // Whether the model walks forward, along its +Z, at the gait's speed. Models moved by
+    // anything else leave this off.
+    pub travels: bool,
+    // Turn of a travelling model in radians per second, which keeps it walking in a circle
+    pub turn_rate: f32,
+}
+
+impl Default for ProceduralGait {
+    fn default() -> Self {
+        let leg = |side: &str, phase: f32| GaitLeg {
+            bones: LegBones {
+                thigh: format!("mixamorig:{side}UpLeg"),
+                shin: format!("mixamorig:{side}Leg"),
+                foot: format!("mixamorig:{side}Foot"),
+            },
+            phase,
+        };
+        ProceduralGait {
+            parameters: GaitParameters::default(),
+            pelvis: "mixamorig:Hips".to_string(),
+            legs: vec![leg("Left", 0.0), leg("Right", 0.5)],
+            travels: false,
+            turn_rate: 0.0,
+        }
+    }
+}
+
+// Bones of the rig with their standing transforms, the feet's turns in the model's space, and
+// the rig measured from them
+struct GaitSetup {
+    pelvis: (Entity, Transform),
+    legs: Vec<(BoneChain, Transform, Transform, Quat)>,
+    rig: GaitRig,
+}
+
+#[derive(Component, Default)]
+struct ProceduralGaitState {
+    setup: Option<GaitSetup>,
+    // Tracks and the parameters and phases they were made from
+    tracks: Option<(GaitParameters, Vec<f32>, GaitTracks)>,
+    phase: f32,
+}
+
+fn find_gait_setup(
+    model: Entity,
+    gait: &ProceduralGait,
+    children: &Query<&Children>,
+    names: &Query<&Name>,
+    parents: &Query<&Parent>,
+    transforms: &Query<&mut Transform>,
+) -> Option<GaitSetup> {
+    let pelvis = find_bone(model, &gait.pelvis, children, names)?;
+    let legs = gait
+        .legs
+        .iter()
+        .map(|leg| BoneChain::find(model, [&leg.bones.thigh, &leg.bones.shin, &leg.bones.foot], children, names))
+        .collect::<Option<Vec<_>>>()?;
+    let to_model = world_transform(model, parents, transforms).affine().inverse();
+    let position = |entity| to_model.transform_point3(world_transform(entity, parents, transforms).translation());
+    let turn = |entity| (GlobalTransform::from(to_model) * world_transform(entity, parents, transforms)).to_scale_rotation_translation().1;
+
+    let leg_length = legs
+        .iter()
+        .map(|leg| position(leg.root).distance(position(leg.middle)) + position(leg.middle).distance(position(leg.end)))
+        .fold(f32::INFINITY, f32::min);
+    let rig = GaitRig {
+        hips: position(pelvis),
+        feet: legs.iter().map(|leg| position(leg.end)).collect(),
+        leg_length,
+    };
+    let legs = legs
+        .into_iter()
+        .map(|leg| Some((leg, *transforms.get(leg.root).ok()?, *transforms.get(leg.middle).ok()?, turn(leg.end))))
+        .collect::<Option<Vec<_>>>()?;
+    Some(GaitSetup { pelvis: (pelvis, *transforms.get(pelvis).ok()?), legs, rig })
+}
+
+fn apply_procedural_gait(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut models: Query<(Entity, &ProceduralGait, Option<&mut ProceduralGaitState>)>,
+    children: Query<&Children>,
+    names: Query<&Name>,
+    parents: Query<&Parent>,
+    mut transforms: Query<&mut Transform>,
+) {
+    for (model, gait, state) in models.iter_mut() {
+        let Some(mut state) = state else {
+            commands.entity(model).insert(ProceduralGaitState::default());
+            continue;
+        };
+        if state.setup.as_ref().is_none_or(|setup| setup.legs.len() != gait.legs.len()) {
+            state.setup = find_gait_setup(model, gait, &children, &names, &parents, &transforms);
+            state.tracks = None;
+        }
+        let state = &mut *state;
+        let Some(setup) = &state.setup else {
+            continue;
+        };
+        let phases: Vec<f32> = gait.legs.iter().map(|leg| leg.phase).collect();
+        if state.tracks.as_ref().is_none_or(|(parameters, previous, _)| *parameters != gait.parameters || *previous != phases) {
+            let tracks = gait.parameters.tracks(&setup.rig, &phases);
+            state.tracks = Some((gait.parameters, phases, tracks));
+        }
+        let Some((_, _, tracks)) = &state.tracks else {
+            continue;
+        };
+
+        state.phase = (state.phase + time.delta_seconds() * gait.parameters.cycles_per_second()).rem_euclid(1.0);
+        let model_world = world_transform(model, &parents, &transforms);
+        let sample = |track: &Curve<VSTransform>| model_world.transform_point(GaitTracks::sample(track, state.phase).transform().translation);
+
+        // Start from the standing pose each frame, so nothing builds up on rigs no clip poses
+        let (pelvis, standing) = setup.pelvis;
+        if let Ok(mut transform) = transforms.get_mut(pelvis) {
+            transform.translation = standing.translation;
+        }
+        let hips = world_transform(pelvis, &parents, &transforms).translation();
+        translate_in_world(pelvis, sample(&tracks.hips) - hips, &parents, &mut transforms);
+
+        let (_, model_rotation, _) = model_world.to_scale_rotation_translation();
+        for ((leg, root, middle, foot), track) in setup.legs.iter().zip(&tracks.feet) {
+            for (bone, standing) in [(leg.root, root), (leg.middle, middle)] {
+                if let Ok(mut transform) = transforms.get_mut(bone) {
+                    transform.rotation = standing.rotation;
+                }
+            }
+            // Knees bend the way the model faces
+            let pole = world_transform(leg.middle, &parents, &transforms).translation() + model_rotation * Vec3::Z;
+            leg.solve(sample(track), Some(pole), 1.0, &parents, &mut transforms);
+
+            // Feet keep the turn they stand with
+            let current = world_transform(leg.end, &parents, &transforms).to_scale_rotation_translation().1;
+            rotate_in_world(leg.end, model_rotation * *foot * current.inverse(), &parents, &mut transforms);
+        }
+    }
+}
+
+// Moves travelling models before their legs are placed, so planted feet stay where they are
+fn move_procedural_gaits(time: Res<Time>, mut models: Query<(&ProceduralGait, &mut Transform)>) {
+    let delta = time.delta_seconds();
+    for (gait, mut transform) in models.iter_mut().filter(|(gait, _)| gait.travels) {
+        let forward = transform.rotation * Vec3::Z;
+        transform.translation += forward * gait.parameters.speed() * delta;
+        transform.rotate_y(gait.turn_rate * delta);
+    }
+}
+
+pub struct EzGaitPlugin;
+
+impl Plugin for EzGaitPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<ProceduralGait>()
+            .add_systems(Update, move_procedural_gaits.run_if(in_state(GameState::Playing)))
+            .add_systems(PostUpdate, apply_procedural_gait
+                .in_set(PostAnimationSet::Pose)
+                .run_if(in_state(GameState::Playing)));
+    }
+}
@@ -0,0 +1,136 @@
+//! Simple ground colliders that feet and other probes can raycast straight down against.
+//!
+//! There is no physics engine in the game, so the ground is described by a few shapes on
+//! entities, placed by their `GlobalTransform`. Only downward rays are needed, which keeps every
+//! shape to a few lines.
+
+use bevy::prelude::*;
+
+#[derive(Component, Reflect, Clone, Default, Debug)]
+#[reflect(Component)]
+pub enum GroundCollider {
+    // Unbounded plane through the entity, facing along its local Y axis
+    #[default]
+    Plane,
+    // Box around the entity with these half extents
+    Box { half_size: Vec3 },
+    Sphere { radius: f32 },
+    // Grid of heights over `size` on the local XZ plane, centred on the entity, with `columns`
+    // heights per row. Heightfields are sampled straight down in local space, so they should
+    // not be tilted.
+    Heightfield { size: Vec2, columns: usize, heights: Vec<f32> },
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct GroundHit {
+    pub point: Vec3,
+    pub normal: Vec3,
+}
+
+impl GroundCollider {
+    // First hit of a ray from `origin` straight down, in the collider's local space
+    fn raycast_local(&self, origin: Vec3, down: Vec3) -> Option<(f32, Vec3)> {
+        match self {
+            GroundCollider::Plane => {
+                let distance = -origin.y / down.y;
+                (down.y < 0.0 && distance >= 0.0).then_some((distance, Vec3::Y))
+            }
+            GroundCollider::Box { half_size } => {
+                // Slab test, keeping the axis the ray enters through
+                let mut entry = (f32::NEG_INFINITY, Vec3::ZERO);
+                let mut exit = f32::INFINITY;
+                for axis in 0..3 {
+                    let normal = Vec3::AXES[axis];
+                    if down[axis].abs() <= f32::EPSILON {
+                        if origin[axis].abs() > half_size[axis] {
+                            return None;
+                        }
+                        continue;
+                    }
+                    let (near, far) = ((-half_size[axis] - origin[axis]) / down[axis], (half_size[axis] - origin[axis]) / down[axis]);
+                    let (near, far, normal) = if near < far { (near, far, -normal) } else { (far, near, normal) };
+                    if near > entry.0 {
+                        entry = (near, normal);
+                    }
+                    exit = exit.min(far);
+                }
+                (entry.0 <= exit && entry.0 >= 0.0).then_some(entry)
+            }
+            GroundCollider::Sphere { radius } => {
+                let along = -origin.dot(down);
+                let closest = origin + down * along;
+                let inside = radius * radius - closest.length_squared();
+                let distance = along - inside.max(0.0).sqrt();
+                (inside >= 0.0 && distance >= 0.0).then(|| (distance, (origin + down * distance).normalize_or_zero()))
+            }
+            GroundCollider::Heightfield { size, columns, heights } => {
+                let (height, normal) = sample_heightfield(*size, *columns, heights, origin.xz())?;
+                let distance = origin.y - height;
+                (distance >= 0.0).then_some((distance, normal))
+            }
+        }
+    }
+
+    // First hit of a ray from `origin` straight down in world space, within `max_distance`
+    pub fn raycast_down(&self, transform: &GlobalTransform, origin: Vec3, max_distance: f32) -> Option<GroundHit> {
+        let to_local = transform.affine().inverse();
+        let local_origin = to_local.transform_point3(origin);
+        let down = to_local.transform_vector3(Vec3::NEG_Y).try_normalize()?;
+        let (distance, normal) = self.raycast_local(local_origin, down)?;
+        let point = transform.transform_point(local_origin + down * distance);
+        let normal = to_local.matrix3.transpose().mul_vec3(normal).normalize_or_zero();
+        (origin.distance(point) <= max_distance).then_some(GroundHit { point, normal })
+    }
+}
+
+// Bilinear height and normal of a heightfield at a local XZ position, if it lies over the grid
+fn sample_heightfield(size: Vec2, columns: usize, heights: &[f32], position: Vec2) -> Option<(f32, Vec3)> {
+    let rows = heights.len().checked_div(columns)?;
+    if columns < 2 || rows < 2 {
+        return None;
+    }
+    let cell = size / Vec2::new(columns as f32 - 1.0, rows as f32 - 1.0);
+    let grid = (position + size * 0.5) / cell;
+    if grid.x < 0.0 || grid.y < 0.0 || grid.x > columns as f32 - 1.0 || grid.y > rows as f32 - 1.0 {
+        return None;
+    }
+    let (column, row) = ((grid.x as usize).min(columns - 2), (grid.y as usize).min(rows - 2));
+    let (u, v) = (grid.x - column as f32, grid.y - row as f32);
+    let height = |column: usize, row: usize| heights[row * columns + column];
+    let (h00, h10, h01, h11) = (height(column, row), height(column + 1, row), height(column, row + 1), height(column + 1, row + 1));
+    let value = (h00 * (1.0 - u) + h10 * u) * (1.0 - v) + (h01 * (1.0 - u) + h11 * u) * v;
+    let slope_x = ((h10 - h00) * (1.0 - v) + (h11 - h01) * v) / cell.x;
+    let slope_z = ((h01 - h00) * (1.0 - u) + (h11 - h10) * u) / cell.y;
+    Some((value, Vec3::new(-slope_x, 1.0, -slope_z).normalize()))
+}
+
+// Closest hit below `origin` among all colliders
+pub fn raycast_ground<'a>(
+    colliders: impl IntoIterator<Item = (&'a GroundCollider, &'a GlobalTransform)>,
+    origin: Vec3,
+    max_distance: f32,
+) -> Option<GroundHit> {
+    colliders
+        .into_iter()
+        .filter_map(|(collider, transform)| collider.raycast_down(transform, origin, max_distance))
+        .min_by(|a, b| origin.distance_squared(a.point).total_cmp(&origin.distance_squared(b.point)))
+}
+
+// Colliders declared in scene files only have a `Transform`
+fn add_collider_global_transforms(
+    mut commands: Commands,
+    colliders: Query<Entity, (With<GroundCollider>, Without<GlobalTransform>)>,
+) {
+    for entity in colliders.iter() {
+        commands.entity(entity).insert(GlobalTransform::default());
+    }
+}
+
+pub struct EzGroundPlugin;
+
+impl Plugin for EzGroundPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<GroundCollider>()
+            .add_systems(Update, add_collider_global_transforms);
+    }
+}
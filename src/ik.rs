@@ -0,0 +1,163 @@
+//! Two-bone inverse kinematics for legs and arms.
+//!
+//! The solver works on the local `Transform`s the animation systems just wrote, so it runs
+//! after sampling and before propagation, and builds world transforms from them itself rather
+//! than reading last frame's `GlobalTransform`. The chain bends in the plane through its root,
+//! the target and a pole point, which by default is where the middle joint already is, so the
+//! knee or elbow keeps pointing the way the animation has it.
+
+use bevy::prelude::*;
+use crate::animator::PostAnimationSet;
+
+// World transform of an entity from its own and its ancestors' local transforms
+pub(crate) fn world_transform(entity: Entity, parents: &Query<&Parent>, transforms: &Query<&mut Transform>) -> GlobalTransform {
+    std::iter::once(entity)
+        .chain(parents.iter_ancestors(entity))
+        .filter_map(|ancestor| transforms.get(ancestor).ok())
+        .fold(GlobalTransform::IDENTITY, |world, local| GlobalTransform::from(*local) * world)
+}
+
+// Named descendant of `root`, such as a bone of a glTF skeleton
+pub(crate) fn find_bone(root: Entity, name: &str, children: &Query<&Children>, names: &Query<&Name>) -> Option<Entity> {
+    children
+        .iter_descendants(root)
+        .find(|descendant| names.get(*descendant).is_ok_and(|bone| bone.as_str() == name))
+}
+
+// Turns `entity` by `rotation` in world space, keeping its position
+pub(crate) fn rotate_in_world(entity: Entity, rotation: Quat, parents: &Query<&Parent>, transforms: &mut Query<&mut Transform>) {
+    let parent_rotation = parents
+        .get(entity)
+        .map_or(Quat::IDENTITY, |parent| world_transform(parent.get(), parents, transforms).compute_transform().rotation);
+    if let Ok(mut transform) = transforms.get_mut(entity) {
+        let world = parent_rotation * transform.rotation;
+        transform.rotation = (parent_rotation.inverse() * rotation * world).normalize();
+    }
+}
+
+// Moves `entity` by `offset` in world space
+pub(crate) fn translate_in_world(entity: Entity, offset: Vec3, parents: &Query<&Parent>, transforms: &mut Query<&mut Transform>) {
+    let parent = parents.get(entity).map_or(GlobalTransform::IDENTITY, |parent| world_transform(parent.get(), parents, transforms));
+    if let Ok(mut transform) = transforms.get_mut(entity) {
+        transform.translation += parent.affine().inverse().transform_vector3(offset);
+    }
+}
+
+// Root, middle and end joints of a limb, like thigh, shin and foot
+#[derive(Clone, Copy, Debug)]
+pub struct BoneChain {
+    pub root: Entity,
+    pub middle: Entity,
+    pub end: Entity,
+}
+
+impl BoneChain {
+    pub(crate) fn find(root: Entity, bones: [&str; 3], children: &Query<&Children>, names: &Query<&Name>) -> Option<Self> {
+        let [first, middle, end] = bones.map(|bone| find_bone(root, bone, children, names));
+        Some(BoneChain { root: first?, middle: middle?, end: end? })
+    }
+
+    // Bends the chain so its end reaches `target`, or points straight at it when out of reach.
+    // `pole` is a world position the middle joint bends towards. `weight` blends from the pose
+    // the chain already has.
+    pub(crate) fn solve(
+        &self,
+        target: Vec3,
+        pole: Option<Vec3>,
+        weight: f32,
+        parents: &Query<&Parent>,
+        transforms: &mut Query<&mut Transform>,
+    ) {
+        let position = |entity, transforms: &Query<&mut Transform>| world_transform(entity, parents, transforms).translation();
+        let (start, middle, end) = (position(self.root, transforms), position(self.middle, transforms), position(self.end, transforms));
+        let (upper, lower) = (start.distance(middle), middle.distance(end));
+        let Some(direction) = (target - start).try_normalize() else {
+            return;
+        };
+        if upper <= f32::EPSILON || lower <= f32::EPSILON {
+            return;
+        }
+
+        // Where the middle joint goes: on the circle of points at the right distance from both
+        // ends, on the pole's side
+        let reach = start.distance(target).clamp((upper - lower).abs() + 1e-4, upper + lower - 1e-4);
+        let along = (upper * upper - lower * lower + reach * reach) / (2.0 * reach);
+        let out = (upper * upper - along * along).max(0.0).sqrt();
+        let bend = (pole.unwrap_or(middle) - start).reject_from_normalized(direction);
+        let bend = bend.try_normalize().unwrap_or_else(|| direction.any_orthonormal_vector());
+        let middle_goal = start + direction * along + bend * out;
+
+        if let (Some(from), Some(to)) = ((middle - start).try_normalize(), (middle_goal - start).try_normalize()) {
+            let turn = Quat::IDENTITY.slerp(Quat::from_rotation_arc(from, to), weight);
+            rotate_in_world(self.root, turn, parents, transforms);
+        }
+
+        // The root's turn moved the lower joints, so measure them again
+        let (middle, end) = (position(self.middle, transforms), position(self.end, transforms));
+        let end_goal = start + direction * reach;
+        if let (Some(from), Some(to)) = ((end - middle).try_normalize(), (end_goal - middle).try_normalize()) {
+            let turn = Quat::IDENTITY.slerp(Quat::from_rotation_arc(from, to), weight);
+            rotate_in_world(self.middle, turn, parents, transforms);
+        }
+    }
+}
+
+#[derive(Reflect, Clone, Debug)]
+pub struct LimbChain {
+    // Bone names from the top of the limb down, e.g. "mixamorig:LeftArm", "mixamorig:LeftForeArm"
+    // and "mixamorig:LeftHand"
+    pub root: String,
+    pub middle: String,
+    pub end: String,
+    // World position the end of the limb reaches for
+    pub target: Vec3,
+    // World position the middle joint bends towards, or the animated bend when `None`
+    pub pole: Option<Vec3>,
+    pub weight: f32,
+}
+
+impl Default for LimbChain {
+    fn default() -> Self {
+        LimbChain {
+            root: String::new(),
+            middle: String::new(),
+            end: String::new(),
+            target: Vec3::ZERO,
+            pole: None,
+            weight: 1.0,
+        }
+    }
+}
+
+// Limbs of a model reaching for targets that gameplay sets
+#[derive(Component, Reflect, Clone, Default, Debug)]
+#[reflect(Component)]
+pub struct LimbIk {
+    pub chains: Vec<LimbChain>,
+}
+
+fn solve_limb_ik(
+    models: Query<(Entity, &LimbIk)>,
+    children: Query<&Children>,
+    names: Query<&Name>,
+    parents: Query<&Parent>,
+    mut transforms: Query<&mut Transform>,
+) {
+    for (model, limbs) in models.iter() {
+        for limb in limbs.chains.iter().filter(|limb| limb.weight > 0.0) {
+            let bones = [limb.root.as_str(), limb.middle.as_str(), limb.end.as_str()];
+            if let Some(chain) = BoneChain::find(model, bones, &children, &names) {
+                chain.solve(limb.target, limb.pole, limb.weight.min(1.0), &parents, &mut transforms);
+            }
+        }
+    }
+}
+
+pub struct EzIkPlugin;
+
+impl Plugin for EzIkPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<LimbIk>()
+            .add_systems(PostUpdate, solve_limb_ik.in_set(PostAnimationSet::Ik));
+    }
+}
@@ -1,33 +1,68 @@
 use bevy::math::VectorSpace;
 use bevy::prelude::*;
 use bevy_inspector_egui::InspectorOptions;
+use serde::{Deserialize, Serialize};
 use crate::GameState;
+use crate::timeline::{advance_timelines, Timeline};
 use crate::vstransform::VSTransform;
+use crate::vstransform2d::{AtlasFrame, VSTransform2d};
+
+// Remaps the interpolation factor through a cardinal spline. Only the tension and points are
+// stored and serialised; the spline is rebuilt from them on load.
+#[derive(Reflect, Clone, Serialize, Deserialize)]
+#[reflect_value(Serialize, Deserialize)]
+#[serde(from = "CurvePoints", into = "CurvePoints")]
+pub struct Curve {
+    tension: f32,
+    points: Vec<f32>,
+    spline: CubicCurve<f32>,
+}
 
-// Define the elerp method for Vec3
-fn elerp(v1: Vec3, v2: Vec3, t: f32) -> Vec3 {
-    let x_factor_log = (1. - t) * v1.x.log2() + t * v2.x.log2();
-    let y_factor_log = (1. - t) * v1.y.log2() + t * v2.y.log2();
-    let z_factor_log = (1. - t) * v1.z.log2() + t * v2.z.log2();
-
-    Vec3::new(
-        x_factor_log.exp2(),
-        y_factor_log.exp2(),
-        z_factor_log.exp2(),
-    )
+#[derive(Serialize, Deserialize)]
+struct CurvePoints {
+    tension: f32,
+    points: Vec<f32>,
 }
 
-#[derive(Reflect, InspectorOptions)]
-pub struct Curve(CubicCurve<f32>);
+impl Curve {
+    pub fn new(tension: f32, points: impl Into<Vec<f32>>) -> Self {
+        let points = points.into();
+        Curve {
+            tension,
+            spline: CubicCardinalSpline::new(tension, points.clone()).to_curve(),
+            points,
+        }
+    }
+
+    pub fn position(&self, t: f32) -> f32 {
+        let segments = self.spline.segments.len();
+        self.spline.position(segments as f32 * t)
+    }
+}
 
 impl Default for Curve {
     fn default() -> Self {
-        Curve(CubicCardinalSpline::new(0.0, [0.0f32, 1.0f32]).to_curve()) // Assuming ForeignType has a `new` method
+        Curve::new(0.0, [0.0f32, 1.0f32])
+    }
+}
+
+impl From<CurvePoints> for Curve {
+    fn from(curve: CurvePoints) -> Self {
+        Curve::new(curve.tension, curve.points)
+    }
+}
+
+impl From<Curve> for CurvePoints {
+    fn from(curve: Curve) -> Self {
+        CurvePoints {
+            tension: curve.tension,
+            points: curve.points,
+        }
     }
 }
 
 // Define the InterpolatableComponent struct
-#[derive(Reflect, Component, Default, InspectorOptions)]
+#[derive(Reflect, Component, Default, InspectorOptions, Serialize, Deserialize)]
 #[reflect(Component)]
 pub struct InterpolatingComponent<T: VectorSpace + Clone + Send + Sync + 'static> {
     start: T,
@@ -53,14 +88,16 @@ impl<T: VectorSpace + Clone + Send + Sync + 'static> InterpolatingComponent<T> {
             start,
             end,
             current: curr,
-            curve: Curve(CubicCardinalSpline::new(0.5, points).to_curve())
+            curve: Curve::new(0.5, points)
         }
     }
 
+    pub fn sample(&self, t: f32) -> T {
+        self.start.lerp(self.end, self.curve.position(t))
+    }
+
     pub fn lerp(&mut self, t: f32) {
-        let segments =self.curve.0.segments.len();
-        let t= self.curve.0.position(segments as f32*t);
-        self.current = self.start.lerp(self.end, t);
+        self.current = self.sample(t);
     }
 }
 
@@ -70,20 +107,25 @@ pub struct InterpolationFactor(pub(crate) f32);
 
 // System to lerp all InterpolatableComponent instances
 fn lerp_system<T: VectorSpace + Clone + Send + Sync + 'static>(
-    mut query: Query<&mut InterpolatingComponent<T>>,
+    mut query: Query<(&mut InterpolatingComponent<T>, Option<&Timeline>)>,
     interpolation_factor: Res<InterpolationFactor>,
 ) {
-    for mut component in query.iter_mut() {
-        component.lerp(interpolation_factor.0);
+    for (mut component, timeline) in query.iter_mut() {
+        component.lerp(timeline.map_or(interpolation_factor.0, Timeline::value));
     }
 }
 
-// System to update the interpolation factor based on the sine of the game time
+// Interpolation factor at a given game time, shared by every time-driven sampler
+pub(crate) fn interpolation_factor_at(seconds: f32) -> f32 {
+    seconds.sin() * 0.5 + 0.5
+}
+
+// System to update the interpolation factor from the shared timeline
 fn update_interpolation_factor_system(
-    time: Res<Time>,
+    timeline: Res<Timeline>,
     mut interpolation_factor: ResMut<InterpolationFactor>,
 ) {
-    interpolation_factor.0 = (time.elapsed_seconds()).sin() * 0.5 + 0.5;
+    interpolation_factor.0 = timeline.value();
 }
 
 // EXAMPLE System to spawn a cube with an InterpolatableComponent wrapping a Transform and Oklaba color
@@ -135,11 +177,11 @@ fn spawn_cube_system(
 struct MaterialHandle(Handle<StandardMaterial>);
 
 // System to update LocalTransform based on the lerpd value
-fn update_local_transform_system(
+pub(crate) fn update_local_transform_system(
     mut query: Query<(&mut Transform, &InterpolatingComponent<VSTransform>)>,
 ) {
     for (mut transform, interpolating_component) in query.iter_mut() {
-        *transform = interpolating_component.current.0;
+        *transform = interpolating_component.current.transform();
     }
 }
 
@@ -171,14 +213,18 @@ impl Plugin for EzInterpolationPlugin {
             .register_type::<InterpolatingComponent<VSTransform>>()
             .register_type::<InterpolatingComponent<Oklaba>>()
             .register_type::<InterpolatingComponent<f32>>()
+            .register_type::<InterpolatingComponent<VSTransform2d>>()
+            .register_type::<InterpolatingComponent<AtlasFrame>>()
             //.add_systems(OnEnter(GameState::Playing),spawn_cube_system)
-            .add_systems(Update, update_interpolation_factor_system.run_if(in_state(GameState::Playing)))
+            .add_systems(Update, update_interpolation_factor_system.after(advance_timelines).run_if(in_state(GameState::Playing)))
             .add_systems(Update,
                          (
                              lerp_system::<VSTransform>,
                              lerp_system::<Oklaba>,
                              lerp_system::<f32>,
-                         ).run_if(in_state(GameState::Playing)))
+                             lerp_system::<VSTransform2d>,
+                             lerp_system::<AtlasFrame>,
+                         ).after(update_interpolation_factor_system).run_if(in_state(GameState::Playing)))
             .add_systems(Update,
                          (
                              update_local_transform_system,
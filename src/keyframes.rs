@@ -4,58 +4,149 @@ use bevy::color::Color::Oklcha;
 use bevy::math::VectorSpace;
 use bevy::prelude::*;
 use bevy_inspector_egui::InspectorOptions;
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
 use crate::GameState;
+use crate::interpolators::InterpolationFactor;
+use crate::timeline::Timeline;
+use crate::quat_spline::sample_uniform;
 use crate::vstransform::VSTransform;
+use crate::vstransform2d::{AtlasFrame, VSTransform2d};
 // Implement InterpolatableValue for Transform
 
-#[derive(Reflect, InspectorOptions)]
-pub struct Curve<T: VectorSpace + Clone + Send + Sync + 'static>(CubicCurve<T>);
+// Cardinal spline through the keyframes. The keys and tension are the source of truth and are
+// what gets serialised; the spline coefficients and key rotations are rebuilt from them on load.
+#[derive(Reflect, Clone, Serialize, Deserialize)]
+#[reflect_value(Serialize, Deserialize)]
+#[serde(from = "CurveKeys<T>", into = "CurveKeys<T>", bound = "T: KeyframeValue")]
+pub struct Curve<T: KeyframeValue> {
+    tension: f32,
+    keys: Vec<T>,
+    spline: CubicCurve<T>,
+    // Rotations of the keys that have one, kept so sampling doesn't collect them every time
+    rotations: Vec<Quat>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "T: KeyframeValue")]
+struct CurveKeys<T: KeyframeValue> {
+    tension: f32,
+    keys: Vec<T>,
+}
 
-impl<T: VectorSpace + VectorSpace + Clone + Send + Sync + 'static> Curve<T> {
+impl<T: KeyframeValue> Curve<T> {
     pub fn new(tension: f32, keyframes: Vec<T>) -> Self {
-        Curve(CubicCardinalSpline::new(tension, keyframes).to_curve())
+        Curve {
+            tension,
+            spline: CubicCardinalSpline::new(tension, keyframes.clone()).to_curve(),
+            rotations: keyframes.iter().filter_map(KeyframeValue::rotation).collect(),
+            keys: keyframes,
+        }
     }
 
     pub fn sample(&self, t: f32) -> T {
-        let segments = self.0.segments.len();
+        let segments = self.spline.segments.len();
         let t = segments as f32 * t;
-        self.0.position(t)
+        self.spline.position(t)
+    }
+
+    pub fn keys(&self) -> &[T] {
+        &self.keys
+    }
+
+    pub fn rotations(&self) -> &[Quat] {
+        &self.rotations
+    }
+
+    // Normalised times at which the curve passes through its keyframes
+    pub fn key_times(&self) -> impl Iterator<Item = f32> {
+        let segments = self.spline.segments.len().max(1);
+        (0..=segments).map(move |i| i as f32 / segments as f32)
+    }
+}
+
+impl<T: KeyframeValue> From<CurveKeys<T>> for Curve<T> {
+    fn from(curve: CurveKeys<T>) -> Self {
+        Curve::new(curve.tension, curve.keys)
+    }
+}
+
+impl<T: KeyframeValue> From<Curve<T>> for CurveKeys<T> {
+    fn from(curve: Curve<T>) -> Self {
+        CurveKeys {
+            tension: curve.tension,
+            keys: curve.keys,
+        }
+    }
+}
+
+// Values that can be keyframed. By default they are sampled straight from the spline;
+// types whose arithmetic is not a true vector space can override parts of the sample.
+pub trait KeyframeValue: VectorSpace + Clone + Send + Sync + Serialize + DeserializeOwned + 'static {
+    fn sample_keyframes(curve: &Curve<Self>, t: f32) -> Self {
+        curve.sample(t)
+    }
+
+    // Rotation of a key, for types whose samples take their rotation from the keys
+    fn rotation(&self) -> Option<Quat> {
+        None
+    }
+}
+
+impl KeyframeValue for f32 {}
+
+impl KeyframeValue for Oklaba {}
+
+impl KeyframeValue for VSTransform2d {}
+
+impl KeyframeValue for AtlasFrame {}
+
+impl KeyframeValue for VSTransform {
+    // The spline works in a single tangent chart at the identity, which distorts rotations far
+    // from it, so the rotation is replaced by a SQUAD spline through the key rotations
+    fn sample_keyframes(curve: &Curve<Self>, t: f32) -> Self {
+        curve.sample(t).with_rotation(sample_uniform(curve.rotations(), t))
+    }
+
+    fn rotation(&self) -> Option<Quat> {
+        Some(self.transform().rotation)
     }
 }
 
 // Define the InterpolatableComponent struct
-#[derive(Reflect, Component, InspectorOptions)]
+#[derive(Reflect, Component, InspectorOptions, Serialize, Deserialize)]
 #[reflect(Component)]
-struct KeyframingComponent<T: VectorSpace + Clone + Send + Sync + 'static> {
-    curve: Curve<T>,
-    current: T,
+#[serde(bound = "T: KeyframeValue")]
+pub struct KeyframingComponent<T: KeyframeValue> {
+    pub(crate) curve: Curve<T>,
+    pub(crate) current: T,
 }
 
-impl<T: VectorSpace + Clone + Send + Sync + 'static> KeyframingComponent<T> {
+impl<T: KeyframeValue> KeyframingComponent<T> {
     pub fn new(tension: f32, keyframes: impl Into<Vec<T>>) -> Self {
         let keyframes_vec: Vec<T> = keyframes.into();
         KeyframingComponent {
-            curve: Curve::new(tension, keyframes_vec.clone()),
             current: keyframes_vec.first().unwrap().clone(),
+            curve: Curve::new(tension, keyframes_vec),
         }
     }
 
+    pub fn sample(&self, t: f32) -> T {
+        T::sample_keyframes(&self.curve, t)
+    }
+
     pub fn interpolate(&mut self, t: f32) {
-        self.current = self.curve.sample(t);
+        self.current = self.sample(t);
     }
 }
 
-// Define a resource to store the interpolation factor
-#[derive(Resource)]
-struct InterpolationFactor(f32);
-
 // System to interpolate all InterpolatableComponent instances
-fn keyframe_system<T: VectorSpace + Clone + Send + Sync + 'static>(
-    mut query: Query<&mut KeyframingComponent<T>>,
+fn keyframe_system<T: KeyframeValue>(
+    mut query: Query<(&mut KeyframingComponent<T>, Option<&Timeline>)>,
     interpolation_factor: Res<InterpolationFactor>,
 ) {
-    for mut component in query.iter_mut() {
-        component.interpolate(interpolation_factor.0);
+    for (mut component, timeline) in query.iter_mut() {
+        component.interpolate(timeline.map_or(interpolation_factor.0, Timeline::value));
     }
 }
 
@@ -112,7 +203,7 @@ fn update_local_transform_system(
     mut query: Query<(&mut Transform, &KeyframingComponent<VSTransform>)>,
 ) {
     for (mut transform, interpolating_component) in query.iter_mut() {
-        *transform = interpolating_component.current.0;
+        *transform = interpolating_component.current.transform();
     }
 }
 
@@ -135,13 +226,6 @@ fn update_color_system(
     }
 }
 
-fn update_interpolation_factor_system(
-    time: Res<Time>,
-    mut interpolation_factor: ResMut<InterpolationFactor>,
-) {
-    interpolation_factor.0 = (time.elapsed_seconds()).sin() * 0.5 + 0.5;
-}
-
 #[derive(Component)]
 struct MaterialHandle(Handle<StandardMaterial>);
 
@@ -150,16 +234,18 @@ pub struct EzKeyframingPlugin;
 
 impl Plugin for EzKeyframingPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(InterpolationFactor(0.0))
-            .register_type::<KeyframingComponent<VSTransform>>()
+        app.register_type::<KeyframingComponent<VSTransform>>()
             .register_type::<KeyframingComponent<Oklaba>>()
             .register_type::<KeyframingComponent<f32>>()
-            .add_systems(Update, update_interpolation_factor_system.run_if(in_state(GameState::Playing)))
+            .register_type::<KeyframingComponent<VSTransform2d>>()
+            .register_type::<KeyframingComponent<AtlasFrame>>()
             .add_systems(Update,
                          (
                              keyframe_system::<VSTransform>,
                              keyframe_system::<Oklaba>,
                              keyframe_system::<f32>,
+                             keyframe_system::<VSTransform2d>,
+                             keyframe_system::<AtlasFrame>,
                          ).run_if(in_state(GameState::Playing)))
             .add_systems(Update,
                          (
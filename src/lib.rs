@@ -8,7 +8,36 @@ mod player;
 mod interpolators;
 mod keyframes;
 mod vstransform;
+mod vstransform2d;
 mod animator;
+mod curve_gizmos;
+mod onion_skin;
+mod clips;
+mod quat_spline;
+mod transform_blend;
+mod written_transform;
+mod sprite_tweens;
+mod timeline;
+mod animation_state;
+mod scene_authoring;
+mod state_machine;
+mod blend_space;
+mod clip_sampling;
+mod root_motion;
+mod stride_matching;
+mod phase_lock;
+mod animation_layers;
+mod clip_library;
+mod clip_events;
+mod ik;
+mod ground;
+mod foot_placement;
+mod look_at;
+mod constraints;
+mod retargeting;
+mod poses;
+mod spring_chains;
+mod gait;
 
 use crate::actions::ActionsPlugin;
 use crate::audio::InternalAudioPlugin;
@@ -21,8 +50,31 @@ use bevy::app::App;
 use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
 use bevy::prelude::*;
 use crate::animator::EzAnimationPlugin;
+use crate::curve_gizmos::EzCurveGizmoPlugin;
+use crate::onion_skin::EzOnionSkinPlugin;
+use crate::clips::EzClipPlugin;
+use crate::sprite_tweens::EzSpriteTweenPlugin;
 use crate::interpolators::EzInterpolationPlugin;
 use crate::keyframes::EzKeyframingPlugin;
+use crate::timeline::EzTimelinePlugin;
+use crate::animation_state::EzAnimationStatePlugin;
+use crate::scene_authoring::EzSceneAuthoringPlugin;
+use crate::state_machine::EzStateMachinePlugin;
+use crate::root_motion::EzRootMotionPlugin;
+use crate::stride_matching::EzStrideMatchingPlugin;
+use crate::phase_lock::EzPhaseLockPlugin;
+use crate::animation_layers::EzAnimationLayerPlugin;
+use crate::clip_library::EzClipLibraryPlugin;
+use crate::clip_events::EzClipEventPlugin;
+use crate::ik::EzIkPlugin;
+use crate::ground::EzGroundPlugin;
+use crate::foot_placement::EzFootPlacementPlugin;
+use crate::look_at::EzLookAtPlugin;
+use crate::constraints::EzConstraintPlugin;
+use crate::retargeting::EzRetargetPlugin;
+use crate::poses::EzPosePlugin;
+use crate::spring_chains::EzSpringChainPlugin;
+use crate::gait::EzGaitPlugin;
 
 // This example game uses States to separate logic
 // See https://bevy-cheatbook.github.io/programming/states.html
@@ -47,10 +99,45 @@ impl Plugin for GamePlugin {
             MenuPlugin,
             ActionsPlugin,
             InternalAudioPlugin,
+            PlayerPlugin,
+        ));
+
+        // Animation tooling
+        app.add_plugins((
+            EzTimelinePlugin,
             EzInterpolationPlugin,
             EzKeyframingPlugin,
+            EzClipPlugin,
+            EzSpriteTweenPlugin,
+            EzSceneAuthoringPlugin,
+            EzCurveGizmoPlugin,
+            EzOnionSkinPlugin,
+            EzAnimationStatePlugin,
+            EzConstraintPlugin,
+        ));
+
+        // Skeletal animation
+        app.add_plugins((
+            EzClipLibraryPlugin,
+            EzRetargetPlugin,
             EzAnimationPlugin,
-            PlayerPlugin,
+            EzStateMachinePlugin,
+            EzAnimationLayerPlugin,
+            EzPosePlugin,
+            EzRootMotionPlugin,
+            EzStrideMatchingPlugin,
+            EzPhaseLockPlugin,
+            EzClipEventPlugin,
+        ));
+
+        // Procedural motion
+        app.add_plugins((
+            EzIkPlugin,
+            EzGroundPlugin,
+            EzFootPlacementPlugin,
+            EzLookAtPlugin,
+            EzSpringChainPlugin,
+            EzGaitPlugin,
         ));
 
         #[cfg(debug_assertions)]
@@ -1,4 +1,6 @@
 use crate::GameState;
+use crate::retargeting::RetargetMap;
+use bevy::gltf::Gltf;
 use bevy::prelude::*;
 use bevy_asset_loader::prelude::*;
 use bevy_kira_audio::AudioSource;
@@ -47,12 +49,21 @@ pub struct TextureAssets {
 
 #[derive(AssetCollection, Resource)]
 pub struct SceneAssets {
+    // Only read through `walker.scn.ron`, but loading it here keeps it ready when that spawns
+    #[allow(dead_code)]
     #[asset(path = "scenes/Walker.glb#Scene0")]
     pub walker: Handle<Scene>,
+    #[asset(path = "scenes/walker.scn.ron")]
+    pub walker_scene: Handle<DynamicScene>,
 }
 
 #[derive(AssetCollection, Resource)]
 pub struct AnimationAssets {
-    #[asset(path = "scenes/Walker.glb#Animation0")]
-    pub walker_walk: Handle<AnimationClip>,
+    // Its named animations end up in the walker's `ClipLibrary`
+    #[asset(path = "scenes/Walker.glb")]
+    pub walker: Handle<Gltf>,
+    // Bakes the walker's clips onto itself as "Retargeted …" clips, which its state machine plays
+    #[allow(dead_code)]
+    #[asset(path = "scenes/walker.retarget.ron")]
+    pub walker_retarget: Handle<RetargetMap>,
 }
@@ -0,0 +1,155 @@
+//! Look-at and aim constraints: turning bones or entities so an axis points at a target.
+//!
+//! The turn is measured from the animated pose each frame, after sampling, so the head keeps
+//! bobbing with the walk while it looks. Several bones can share one constraint, like spine,
+//! neck and head; from the bottom up each one turns by its weight of what is still left, within
+//! its limits, and the bones above see the result.
+
+use bevy::prelude::*;
+use crate::animator::PostAnimationSet;
+use crate::ik::{find_bone, world_transform};
+
+#[derive(Reflect, Clone, Debug)]
+pub enum LookTarget {
+    // World position
+    Point(Vec3),
+    Entity(Entity),
+    // First entity with this `Name`, which scene files can refer to
+    Named(String),
+}
+
+impl Default for LookTarget {
+    fn default() -> Self {
+        LookTarget::Point(Vec3::ZERO)
+    }
+}
+
+#[derive(Reflect, Clone, Default, Debug)]
+pub struct LookAtBone {
+    pub name: String,
+    // Share of the remaining turn this bone takes
+    pub weight: f32,
+}
+
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct LookAt {
+    pub target: LookTarget,
+    // Bones below the entity that turn, bottom first. When empty the entity itself turns.
+    pub bones: Vec<LookAtBone>,
+    // Axis in the local space of each bone or the entity that points at the target
+    pub forward: Vec3,
+    // Most each bone turns from its animated pose around its local X, Y and Z axes, in radians
+    pub limits: Vec3,
+    pub weight: f32,
+    // Seconds to close most of the gap to the target direction, zero to follow it at once
+    pub smoothing: f32,
+}
+
+impl Default for LookAt {
+    fn default() -> Self {
+        LookAt {
+            target: LookTarget::default(),
+            bones: Vec::new(),
+            forward: Vec3::Z,
+            limits: Vec3::splat(std::f32::consts::PI),
+            weight: 1.0,
+            smoothing: 0.2,
+        }
+    }
+}
+
+// Turn applied to each bone last frame, relative to its animated pose, and the rotation that
+// left it with. Entities that nothing else poses still have that rotation the next frame, and
+// the turn is taken off again to find their own pose.
+#[derive(Component, Default)]
+struct LookAtState {
+    turns: Vec<(Quat, Option<Quat>)>,
+}
+
+// `rotation` with its angles around each local axis clamped to `limits`
+fn limit_rotation(rotation: Quat, limits: Vec3) -> Quat {
+    let (y, x, z) = rotation.to_euler(EulerRot::YXZ);
+    Quat::from_euler(
+        EulerRot::YXZ,
+        y.clamp(-limits.y, limits.y),
+        x.clamp(-limits.x, limits.x),
+        z.clamp(-limits.z, limits.z),
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn apply_look_at(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut constraints: Query<(Entity, &LookAt, Option<&mut LookAtState>)>,
+    named: Query<(Entity, &Name)>,
+    children: Query<&Children>,
+    names: Query<&Name>,
+    parents: Query<&Parent>,
+    mut transforms: Query<&mut Transform>,
+) {
+    let delta = time.delta_seconds();
+    for (entity, look_at, state) in constraints.iter_mut() {
+        let Some(mut state) = state else {
+            commands.entity(entity).insert(LookAtState::default());
+            continue;
+        };
+        let target = match &look_at.target {
+            LookTarget::Point(point) => Some(*point),
+            LookTarget::Entity(target) => Some(world_transform(*target, &parents, &transforms).translation()),
+            LookTarget::Named(name) => named
+                .iter()
+                .find(|(_, target)| target.as_str() == name)
+                .map(|(target, _)| world_transform(target, &parents, &transforms).translation()),
+        };
+        let Some(target) = target else {
+            continue;
+        };
+
+        let turned: Vec<(Entity, f32)> = if look_at.bones.is_empty() {
+            vec![(entity, 1.0)]
+        } else {
+            look_at
+                .bones
+                .iter()
+                .filter_map(|bone| Some((find_bone(entity, &bone.name, &children, &names)?, bone.weight)))
+                .collect()
+        };
+        state.turns.resize(turned.len(), (Quat::IDENTITY, None));
+        let blend = if look_at.smoothing > 0.0 { 1.0 - (-delta / look_at.smoothing).exp() } else { 1.0 };
+
+        for ((bone, share), (previous, written)) in turned.into_iter().zip(state.turns.iter_mut()) {
+            let Ok(local) = transforms.get(bone).copied() else {
+                continue;
+            };
+            let pose = match *written {
+                Some(written) if written == local.rotation => (written * previous.inverse()).normalize(),
+                _ => local.rotation,
+            };
+            let parent = parents.get(bone).map_or(GlobalTransform::IDENTITY, |parent| world_transform(parent.get(), &parents, &transforms));
+            let world = parent * GlobalTransform::from(local.with_rotation(pose));
+            let (_, rotation, position) = world.to_scale_rotation_translation();
+            let (Some(aim), Some(wanted)) = ((rotation * look_at.forward).try_normalize(), (target - position).try_normalize()) else {
+                continue;
+            };
+            // Turn in the bone's own space, where the limits apply
+            let turn = rotation.inverse() * Quat::from_rotation_arc(aim, wanted) * rotation;
+            let turn = Quat::IDENTITY.slerp(limit_rotation(turn, look_at.limits), share * look_at.weight);
+            *previous = previous.slerp(turn, blend);
+            if let Ok(mut transform) = transforms.get_mut(bone) {
+                transform.rotation = (pose * *previous).normalize();
+                *written = Some(transform.rotation);
+            }
+        }
+    }
+}
+
+pub struct EzLookAtPlugin;
+
+impl Plugin for EzLookAtPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<LookAt>()
+            .add_systems(PostUpdate, apply_look_at.in_set(PostAnimationSet::Ik));
+    }
+}
@@ -36,7 +36,7 @@ struct Menu;
 
 fn setup_menu(mut commands: Commands, textures: Res<TextureAssets>) {
     info!("menu");
-    commands.spawn(Camera3dBundle::default());
+    commands.spawn((Camera3dBundle::default(), Name::new("Camera")));
     commands
         .spawn((
             NodeBundle {
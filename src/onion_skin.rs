@@ -0,0 +1,233 @@
+//! Onion-skinning: translucent ghosts of an animated entity at past and future sample times.
+
+use bevy::prelude::*;
+use crate::GameState;
+use crate::interpolators::InterpolatingComponent;
+use crate::keyframes::KeyframingComponent;
+use crate::timeline::Timeline;
+use crate::vstransform::VSTransform;
+
+// Add to an animated entity to draw ghosts of it around the current time
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct OnionSkin {
+    // Number of ghosts drawn before and after the current time
+    pub steps_before: usize,
+    pub steps_after: usize,
+    // Time in seconds between two consecutive ghosts
+    pub spacing: f32,
+    // Opacity of the ghosts closest to the current time
+    pub max_alpha: f32,
+    // Tints used when the entity has no colour track
+    pub past_color: Color,
+    pub future_color: Color,
+}
+
+impl Default for OnionSkin {
+    fn default() -> Self {
+        OnionSkin {
+            steps_before: 3,
+            steps_after: 3,
+            spacing: 0.1,
+            max_alpha: 0.5,
+            past_color: Color::linear_rgb(1.0, 0.3, 0.3),
+            future_color: Color::linear_rgb(0.3, 1.0, 0.3),
+        }
+    }
+}
+
+impl OnionSkin {
+    // Signed step offsets of every ghost, e.g. [-2, -1, 1, 2]
+    fn offsets(&self) -> impl Iterator<Item = i32> {
+        let before = -(self.steps_before as i32)..0;
+        let after = 1..=self.steps_after as i32;
+        before.chain(after)
+    }
+
+    // Opacity of a ghost, fading linearly towards the outermost step
+    fn alpha(&self, offset: i32) -> f32 {
+        let steps = if offset < 0 { self.steps_before } else { self.steps_after };
+        let falloff = 1.0 - (offset.unsigned_abs() as f32 - 1.0) / steps as f32;
+        self.max_alpha * falloff
+    }
+}
+
+// A single ghost mesh, mirroring one mesh entity of the owner's hierarchy
+#[derive(Component)]
+struct OnionGhost {
+    owner: Entity,
+    source: Entity,
+    offset: i32,
+    material: Handle<StandardMaterial>,
+}
+
+// Ghosts spawned for an owner, rebuilt whenever its meshes or step counts change
+#[derive(Component, Default)]
+struct OnionGhosts {
+    sources: Vec<Entity>,
+    offsets: Vec<i32>,
+    ghosts: Vec<Entity>,
+}
+
+// Collects every mesh entity in the hierarchy below (and including) the owner
+fn collect_meshes(
+    entity: Entity,
+    children: &Query<&Children>,
+    meshes: &Query<&Handle<Mesh>>,
+    out: &mut Vec<Entity>,
+) {
+    if meshes.contains(entity) {
+        out.push(entity);
+    }
+    if let Ok(entity_children) = children.get(entity) {
+        for &child in entity_children.iter() {
+            collect_meshes(child, children, meshes, out);
+        }
+    }
+}
+
+// Spawns or respawns the ghosts of every onion-skinned entity.
+// Skinned meshes are drawn in their bind pose, since skinning would snap them back to the live joints.
+fn spawn_onion_ghosts(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut owners: Query<(Entity, &OnionSkin, Option<&mut OnionGhosts>)>,
+    children: Query<&Children>,
+    meshes: Query<&Handle<Mesh>>,
+) {
+    for (owner, onion_skin, ghosts) in owners.iter_mut() {
+        let mut sources = Vec::new();
+        collect_meshes(owner, &children, &meshes, &mut sources);
+        let offsets: Vec<i32> = onion_skin.offsets().collect();
+
+        let mut ghosts = match ghosts {
+            Some(ghosts) if ghosts.sources == sources && ghosts.offsets == offsets => continue,
+            Some(ghosts) => ghosts,
+            None => {
+                commands.entity(owner).insert(OnionGhosts::default());
+                continue;
+            }
+        };
+
+        for ghost in ghosts.ghosts.drain(..) {
+            commands.entity(ghost).despawn_recursive();
+        }
+        for &source in sources.iter() {
+            let Ok(mesh) = meshes.get(source) else {
+                continue;
+            };
+            for &offset in offsets.iter() {
+                let material = materials.add(StandardMaterial {
+                    alpha_mode: AlphaMode::Blend,
+                    unlit: true,
+                    ..default()
+                });
+                let ghost = commands
+                    .spawn((
+                        PbrBundle {
+                            mesh: mesh.clone(),
+                            material: material.clone(),
+                            ..default()
+                        },
+                        OnionGhost {
+                            owner,
+                            source,
+                            offset,
+                            material,
+                        },
+                        Name::new(format!("Onion ghost {offset:+}")),
+                    ))
+                    .id();
+                ghosts.ghosts.push(ghost);
+            }
+        }
+        ghosts.sources = sources;
+        ghosts.offsets = offsets;
+    }
+}
+
+fn despawn_orphaned_ghosts(
+    mut commands: Commands,
+    ghosts: Query<(Entity, &OnionGhost)>,
+    owners: Query<(), With<OnionSkin>>,
+    disabled: Query<Entity, (With<OnionGhosts>, Without<OnionSkin>)>,
+) {
+    for (entity, ghost) in ghosts.iter() {
+        if !owners.contains(ghost.owner) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+    for owner in disabled.iter() {
+        commands.entity(owner).remove::<OnionGhosts>();
+    }
+}
+
+// Moves and tints every ghost to the owner's transform and colour tracks at its sample time
+fn update_onion_ghosts(
+    shared_timeline: Res<Timeline>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut ghosts: Query<(&OnionGhost, &mut Transform), Without<OnionSkin>>,
+    owners: Query<(
+        &OnionSkin,
+        &GlobalTransform,
+        Option<&Parent>,
+        Option<&InterpolatingComponent<VSTransform>>,
+        Option<&KeyframingComponent<VSTransform>>,
+        Option<&InterpolatingComponent<Oklaba>>,
+        Option<&KeyframingComponent<Oklaba>>,
+        Option<&Timeline>,
+    )>,
+    globals: Query<&GlobalTransform>,
+) {
+    for (ghost, mut transform) in ghosts.iter_mut() {
+        let Ok((onion_skin, owner_global, parent, transform_tween, transform_keys, color_tween, color_keys, timeline)) =
+            owners.get(ghost.owner)
+        else {
+            continue;
+        };
+        let Ok(source_global) = globals.get(ghost.source) else {
+            continue;
+        };
+
+        let t = timeline.unwrap_or(&*shared_timeline).value_after(ghost.offset as f32 * onion_skin.spacing);
+
+        // Owner transform at the ghost's time, expressed in the owner's parent space
+        let sampled = match (transform_tween, transform_keys) {
+            (Some(tween), _) => Some(tween.sample(t).transform()),
+            (None, Some(keys)) => Some(keys.sample(t).transform()),
+            (None, None) => None,
+        };
+        let owner_at_t = match (sampled, parent.and_then(|parent| globals.get(parent.get()).ok())) {
+            (Some(local), Some(parent_global)) => parent_global.mul_transform(local),
+            (Some(local), None) => GlobalTransform::from(local),
+            (None, _) => *owner_global,
+        };
+        // Keep the source mesh's offset from the owner, so whole hierarchies are ghosted
+        let relative = owner_global.affine().inverse() * source_global.affine();
+        *transform = owner_at_t.mul_transform(Transform::from_matrix(relative.into())).compute_transform();
+
+        let color = match (color_tween, color_keys) {
+            (Some(tween), _) => Color::from(tween.sample(t)),
+            (None, Some(keys)) => Color::from(keys.sample(t)),
+            (None, None) if ghost.offset < 0 => onion_skin.past_color,
+            (None, None) => onion_skin.future_color,
+        };
+        if let Some(material) = materials.get_mut(&ghost.material) {
+            material.base_color = color.with_alpha(onion_skin.alpha(ghost.offset));
+        }
+    }
+}
+
+pub struct EzOnionSkinPlugin;
+
+impl Plugin for EzOnionSkinPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<OnionSkin>()
+            .add_systems(Update,
+                         (
+                             despawn_orphaned_ghosts,
+                             spawn_onion_ghosts,
+                             update_onion_ghosts,
+                         ).chain().run_if(in_state(GameState::Playing)));
+    }
+}
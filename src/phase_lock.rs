@@ -0,0 +1,103 @@
+//! Phase locking: driving a skeletal clip's time from a timeline instead of letting it play.
+//!
+//! The main animation of every `AnimationTransitions` player below a `PhaseLock`, or every clip
+//! of the current and fading states of one a state machine runs, is seeked each frame to the
+//! phase read from the entity's `Timeline`, or the shared one. The seek happens after Bevy
+//! advances the players and before it samples them, so the clips show exactly that phase
+//! whatever their speed, and scrubbing the timeline backwards plays the cycle backwards.
+
+use bevy::animation::{advance_animations, animate_targets};
+use bevy::prelude::*;
+use crate::state_machine::ActiveStateMachine;
+use crate::timeline::Timeline;
+
+// Which value of the timeline gives the phase
+#[derive(Reflect, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum PhaseSource {
+    // The interpolation factor, i.e. progress along whatever the timeline moves back and forth
+    #[default]
+    Value,
+    // Position within the timeline's period, which only runs one way
+    Cycle,
+}
+
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct PhaseLock {
+    pub source: PhaseSource,
+    // Clip cycles per unit of the source, e.g. the number of steps along a path
+    pub cycles: f32,
+    // Phase of the clip when the source is zero
+    pub offset: f32,
+}
+
+impl Default for PhaseLock {
+    fn default() -> Self {
+        PhaseLock {
+            source: PhaseSource::default(),
+            cycles: 1.0,
+            offset: 0.0,
+        }
+    }
+}
+
+impl PhaseLock {
+    pub fn phase(&self, timeline: &Timeline) -> f32 {
+        let source = match self.source {
+            PhaseSource::Value => timeline.value(),
+            PhaseSource::Cycle => timeline.cycle(),
+        };
+        (source * self.cycles + self.offset).rem_euclid(1.0)
+    }
+}
+
+fn seek_phase_locked_animations(
+    clips: Res<Assets<AnimationClip>>,
+    graphs: Res<Assets<AnimationGraph>>,
+    shared_timeline: Res<Timeline>,
+    locks: Query<(&PhaseLock, Option<&Timeline>)>,
+    parents: Query<&Parent>,
+    mut players: Query<(Entity, &AnimationTransitions, &mut AnimationPlayer, &Handle<AnimationGraph>, Option<&ActiveStateMachine>)>,
+) {
+    for (entity, transitions, mut player, graph, machine) in players.iter_mut() {
+        let Some((lock, timeline)) = std::iter::once(entity)
+            .chain(parents.iter_ancestors(entity))
+            .find_map(|ancestor| locks.get(ancestor).ok()) else {
+            continue;
+        };
+        let phase = lock.phase(timeline.unwrap_or(&*shared_timeline));
+
+        // The state machine's clips all share the phase, so its blends stay in step
+        if let Some(machine) = machine {
+            for (node, clip) in machine.active_clips() {
+                if let (Some(clip), Some(animation)) = (clips.get(clip), player.animation_mut(*node)) {
+                    animation.seek_to(phase * clip.duration());
+                }
+            }
+            continue;
+        }
+        let Some(node) = transitions.get_main_animation() else {
+            continue;
+        };
+        let duration = graphs
+            .get(graph)
+            .and_then(|graph| graph.get(node))
+            .and_then(|node| node.clip.as_ref())
+            .and_then(|clip| clips.get(clip))
+            .map(AnimationClip::duration);
+        if let (Some(duration), Some(animation)) = (duration, player.animation_mut(node)) {
+            animation.seek_to(phase * duration);
+        }
+    }
+}
+
+pub struct EzPhaseLockPlugin;
+
+impl Plugin for EzPhaseLockPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<PhaseLock>()
+            .add_systems(PostUpdate, seek_phase_locked_animations
+                .after(advance_animations)
+                .before(animate_targets));
+    }
+}
@@ -4,24 +4,24 @@ use crate::GameState;
 use bevy::prelude::*;
 use std::time::Duration;
 use bevy::animation::{animate_targets, RepeatAnimation};
-use crate::interpolators::{InterpolatingComponent, InterpolationFactor};
-use crate::vstransform::VSTransform;
 
 pub struct PlayerPlugin;
 
-#[derive(Component)]
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
 pub struct Player;
 
 /// This plugin handles player related stuff like movement
 /// Player logic is only active during the State `GameState::Playing`
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(GameState::Playing), spawn_walker)
-            .add_systems(Update, sync_animation_speed.run_if(in_state(GameState::Playing)));
+        app.register_type::<Player>()
+            .add_systems(OnEnter(GameState::Playing), spawn_walker);
             /*add_systems(Update, move_player.run_if(in_state(GameState::Playing)));*/
     }
 }
 
+// The walker, its tweens and timelines are declared in `assets/scenes/walker.scn.ron`
 fn spawn_walker(
     mut commands: Commands,
     scene_assets: Res<SceneAssets>,
@@ -31,42 +31,8 @@ fn spawn_walker(
         brightness: 300.0,
     });
 
-    let start_transform = Transform {
-        translation: Vec3::new(-1.8, -1.0, -5.0),
-        rotation: Quat::from_rotation_y(std::f32::consts::FRAC_PI_2), // 90 degrees rotation around Y-axis
+    commands.spawn(DynamicSceneBundle {
+        scene: scene_assets.walker_scene.clone(),
         ..default()
-    };
-
-    let end_transform = Transform {
-        translation: Vec3::new(1.8, -1.0, -5.0),
-        rotation: Quat::from_rotation_y(std::f32::consts::FRAC_PI_2), // 90 degrees rotation around Y-axis
-        ..default()
-    };
-
-
-    commands.spawn((
-        SceneBundle {
-            scene: scene_assets.walker.clone(),
-            transform: Transform {
-                translation: default(),
-                rotation: Quat::from_rotation_y(std::f32::consts::FRAC_PI_2), // 90 degrees rotation around Y-axis
-                ..default()
-            },
-            ..default()
-        },
-    ))
-        .insert(InterpolatingComponent::<VSTransform>::standard(start_transform.into(), end_transform.into()));
+    });
 }
-
-// System to update the AnimationPlayer's speed based on InterpolatingComponent<f32>
-fn sync_animation_speed(
-    mut query: Query<(&mut AnimationPlayer)>,
-    time: ResMut<Time>,
-) {
-    for (mut player) in query.iter_mut() {
-        for (_, playing_animation) in player.playing_animations_mut() {
-            let new_speed = time.elapsed_seconds().cos();
-            playing_animation.set_speed(new_speed);
-        }
-    }
-}
\ No newline at end of file
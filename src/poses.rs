@@ -0,0 +1,231 @@
+//! Poses: the local transforms of a hierarchy, captured into assets and blended back on.
+//!
+//! A `Pose` holds a transform for every named entity below a root, by its path of names from
+//! the root, like "Armature/mixamorig:Hips/mixamorig:Spine", so it fits any instance of the same
+//! model. Poses are captured with a `CapturePose` event and can be saved as `.pose.ron` files,
+//! which load back as assets. A `PoseBlend` on a root moves each entity from the transform it
+//! already has towards its transform in each pose in turn, by the layer's weight times the
+//! bone's, with `VSTransform` interpolation so each bone's scale moves the way its pose says.
+
+use std::collections::BTreeMap;
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
+use bevy::asset::io::Reader;
+use bevy::math::VectorSpace;
+use bevy::prelude::*;
+use bevy::scene::ron;
+use bevy::utils::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::animator::PostAnimationSet;
+use crate::vstransform::VSTransform;
+use crate::written_transform::WrittenTransform;
+
+#[derive(Asset, TypePath, Serialize, Deserialize, Clone, Default, Debug)]
+pub struct Pose {
+    pub bones: BTreeMap<String, VSTransform>,
+}
+
+// Named entities below `root`, outermost first, with their paths of names from it. Unnamed
+// entities are left out of the paths.
+fn named_descendants(root: Entity, children: &Query<&Children>, names: &Query<&Name>) -> Vec<(Entity, String)> {
+    fn visit(entity: Entity, path: &str, children: &Query<&Children>, names: &Query<&Name>, found: &mut Vec<(Entity, String)>) {
+        for child in children.get(entity).into_iter().flatten() {
+            let path = match names.get(*child) {
+                Ok(name) if path.is_empty() => name.to_string(),
+                Ok(name) => format!("{path}/{name}"),
+                Err(_) => path.to_string(),
+            };
+            if names.contains(*child) {
+                found.push((*child, path.clone()));
+            }
+            visit(*child, &path, children, names, found);
+        }
+    }
+
+    let mut found = Vec::new();
+    visit(root, "", children, names, &mut found);
+    found
+}
+
+impl Pose {
+    pub fn capture(root: Entity, children: &Query<&Children>, names: &Query<&Name>, transforms: &Query<&Transform>) -> Self {
+        let bones = named_descendants(root, children, names)
+            .into_iter()
+            .filter_map(|(entity, path)| Some((path, VSTransform::from(*transforms.get(entity).ok()?))))
+            .collect();
+        Pose { bones }
+    }
+}
+
+#[derive(Default)]
+struct PoseLoader;
+
+impl AssetLoader for PoseLoader {
+    type Asset = Pose;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Pose, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["pose.ron"]
+    }
+}
+
+// Captures the pose of everything below `root`. With a path like "poses/crouch.pose.ron" it is
+// also written to the assets folder, except on the web, to load back from that path later.
+// Sending these and reading the answers is up to gameplay code, which nothing in the demo does
+// yet.
+#[allow(dead_code)]
+#[derive(Event, Clone, Debug)]
+pub struct CapturePose {
+    pub root: Entity,
+    pub save_as: Option<String>,
+}
+
+#[allow(dead_code)]
+#[derive(Event, Clone, Debug)]
+pub struct PoseCaptured {
+    pub root: Entity,
+    pub pose: Handle<Pose>,
+}
+
+#[derive(Reflect, Clone, Debug)]
+pub struct PoseLayer {
+    pub pose: Handle<Pose>,
+    pub weight: f32,
+    // How much of `weight` applies to each bone, by its name. Bones not listed take
+    // `default_bone_weight`.
+    pub bone_weights: HashMap<String, f32>,
+    pub default_bone_weight: f32,
+}
+
+impl Default for PoseLayer {
+    fn default() -> Self {
+        PoseLayer {
+            pose: Handle::default(),
+            weight: 1.0,
+            bone_weights: HashMap::new(),
+            default_bone_weight: 1.0,
+        }
+    }
+}
+
+// Poses blended over a hierarchy, from the first layer up
+#[derive(Component, Reflect, Clone, Default, Debug)]
+#[reflect(Component)]
+pub struct PoseBlend {
+    pub layers: Vec<PoseLayer>,
+}
+
+// What the blend wrote to each entity
+#[derive(Component, Default)]
+struct PoseBlendState {
+    written: HashMap<Entity, WrittenTransform>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_pose(path: &str, text: &str) -> Result<(), String> {
+    fs::write(Path::new("assets").join(path), text).map_err(|error| error.to_string())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write_pose(_path: &str, _text: &str) -> Result<(), String> {
+    Err("there is no file system to save to".to_string())
+}
+
+fn capture_poses(
+    mut requests: EventReader<CapturePose>,
+    mut captured: EventWriter<PoseCaptured>,
+    mut poses: ResMut<Assets<Pose>>,
+    children: Query<&Children>,
+    names: Query<&Name>,
+    transforms: Query<&Transform>,
+) {
+    for request in requests.read() {
+        let pose = Pose::capture(request.root, &children, &names, &transforms);
+        if let Some(path) = &request.save_as {
+            let result = ron::ser::to_string_pretty(&pose, ron::ser::PrettyConfig::default())
+                .map_err(|error| error.to_string())
+                .and_then(|text| write_pose(path, &text));
+            match result {
+                Ok(()) => info!("saved pose to {path}"),
+                Err(error) => warn!("could not save pose: {error}"),
+            }
+        }
+        captured.send(PoseCaptured { root: request.root, pose: poses.add(pose) });
+    }
+}
+
+fn blend_poses(
+    mut commands: Commands,
+    mut blends: Query<(Entity, &PoseBlend, Option<&mut PoseBlendState>)>,
+    poses: Res<Assets<Pose>>,
+    children: Query<&Children>,
+    names: Query<&Name>,
+    mut transforms: Query<&mut Transform>,
+) {
+    for (root, blend, state) in blends.iter_mut() {
+        let Some(mut state) = state else {
+            commands.entity(root).insert(PoseBlendState::default());
+            continue;
+        };
+        let layers: Vec<(&PoseLayer, &Pose)> = blend
+            .layers
+            .iter()
+            .filter(|layer| layer.weight > 0.0)
+            .filter_map(|layer| Some((layer, poses.get(&layer.pose)?)))
+            .collect();
+
+        let mut written = HashMap::new();
+        for (entity, path) in named_descendants(root, &children, &names) {
+            let Ok(mut transform) = transforms.get_mut(entity) else {
+                continue;
+            };
+            let mut entity_written = state.written.get(&entity).copied().unwrap_or_default();
+            let input = entity_written.input(*transform);
+            let mut local = input;
+            let name = names.get(entity).map_or("", Name::as_str);
+            for (layer, pose) in &layers {
+                let Some(posed) = pose.bones.get(&path) else {
+                    continue;
+                };
+                let bone_weight = layer.bone_weights.get(name).copied().unwrap_or(layer.default_bone_weight);
+                let weight = (layer.weight * bone_weight).clamp(0.0, 1.0);
+                if weight > 0.0 {
+                    local = VSTransform::from(local).lerp(*posed, weight).transform();
+                }
+            }
+            *transform = local;
+            entity_written.write(input, local);
+            written.insert(entity, entity_written);
+        }
+        state.written = written;
+    }
+}
+
+pub struct EzPosePlugin;
+
+impl Plugin for EzPosePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Pose>()
+            .init_asset_loader::<PoseLoader>()
+            .register_type::<PoseBlend>()
+            .add_event::<CapturePose>()
+            .add_event::<PoseCaptured>()
+            .add_systems(Update, capture_poses)
+            .add_systems(PostUpdate, blend_poses.in_set(PostAnimationSet::Pose));
+    }
+}
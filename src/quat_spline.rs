@@ -0,0 +1,112 @@
+//! Rotation splines on the unit quaternion sphere (SQUAD).
+//!
+//! Componentwise splines over quaternions leave the unit sphere and do not
+//! interpolate angles correctly. SQUAD nests slerps between the keys and two
+//! inner control points per key, which keeps the result unit length. The control
+//! points are chosen from the neighbouring keys and their times so the angular
+//! velocity is continuous across keys, also for unevenly spaced keys.
+
+use bevy::math::{Quat, Vec3};
+
+// Logarithm of a unit quaternion, as the rotation vector scaled by half the angle
+pub fn quat_log(q: Quat) -> Vec3 {
+    let v = Vec3::new(q.x, q.y, q.z);
+    let sin = v.length();
+    if sin <= f32::EPSILON {
+        return Vec3::ZERO;
+    }
+    v * (sin.atan2(q.w) / sin)
+}
+
+// Inverse of `quat_log`
+pub fn quat_exp(v: Vec3) -> Quat {
+    let angle = v.length();
+    if angle <= f32::EPSILON {
+        return Quat::from_xyzw(v.x, v.y, v.z, 1.0).normalize();
+    }
+    let axis = v * (angle.sin() / angle);
+    Quat::from_xyzw(axis.x, axis.y, axis.z, angle.cos())
+}
+
+// Flips `q` into the same hemisphere as `reference`, so slerps take the short way round
+fn align(reference: Quat, q: Quat) -> Quat {
+    if reference.dot(q) < 0.0 {
+        -q
+    } else {
+        q
+    }
+}
+
+// Classic SQUAD between `q0` and `q1` with inner control points `a` and `b`
+pub fn squad(q0: Quat, a: Quat, b: Quat, q1: Quat, t: f32) -> Quat {
+    let outer = q0.slerp(q1, t);
+    let inner = a.slerp(b, t);
+    outer.slerp(inner, 2.0 * t * (1.0 - t)).normalize()
+}
+
+// Angular tangent (log space, per unit time) at key `q`, from the offsets to its neighbours.
+// Missing neighbours give a one-sided estimate, so the spline eases into the end keys.
+fn tangent(to_prev: Option<(Vec3, f32)>, to_next: Option<(Vec3, f32)>) -> Vec3 {
+    match (to_prev, to_next) {
+        (Some((prev, dt_prev)), Some((next, dt_next))) => {
+            // Non-uniform Catmull-Rom weighting of the incoming and outgoing slopes
+            (next / dt_next * dt_prev - prev / dt_prev * dt_next) / (dt_prev + dt_next)
+        }
+        (None, Some((next, dt_next))) => next / dt_next,
+        (Some((prev, dt_prev)), None) => -prev / dt_prev,
+        (None, None) => Vec3::ZERO,
+    }
+}
+
+// Interpolates the segment from `start` to `end` at local factor `t`, using the keys either side
+// of it (if any) to keep the angular velocity continuous. Keys are `(time, rotation)` pairs.
+pub fn squad_segment(
+    prev: Option<(f32, Quat)>,
+    start: (f32, Quat),
+    end: (f32, Quat),
+    next: Option<(f32, Quat)>,
+    t: f32,
+) -> Quat {
+    let (t0, q0) = (start.0, start.1.normalize());
+    let (t1, q1) = (end.0, align(q0, end.1.normalize()));
+    let dt = (t1 - t0).max(f32::EPSILON);
+
+    // Log-space offsets from each end of the segment to its neighbours
+    let forward = quat_log(q0.inverse() * q1);
+    let before = prev.map(|(time, q)| {
+        (quat_log(q0.inverse() * align(q0, q.normalize())), (t0 - time).max(f32::EPSILON))
+    });
+    let after = next.map(|(time, q)| {
+        (quat_log(q1.inverse() * align(q1, q.normalize())), (time - t1).max(f32::EPSILON))
+    });
+
+    // Outgoing control point at the start key and incoming control point at the end key
+    let start_tangent = tangent(before, Some((forward, dt)));
+    let end_tangent = tangent(Some((-forward, dt)), after);
+    let a = q0 * quat_exp((start_tangent * dt - forward) * 0.5);
+    let b = q1 * quat_exp((forward - end_tangent * dt) * 0.5);
+
+    squad(q0, a, b, q1, t)
+}
+
+// Samples evenly spaced rotation keys at normalised `t`, the first key at 0 and the last at 1
+pub fn sample_uniform(keys: &[Quat], t: f32) -> Quat {
+    let Some(last) = keys.len().checked_sub(1) else {
+        return Quat::IDENTITY;
+    };
+    if last == 0 {
+        return keys[0].normalize();
+    }
+
+    let position = (t * last as f32).clamp(0.0, last as f32);
+    let index = (position.floor() as usize).min(last - 1);
+    let key = |i: usize| (i as f32, keys[i]);
+
+    squad_segment(
+        index.checked_sub(1).map(key),
+        key(index),
+        key(index + 1),
+        (index + 2 <= last).then(|| key(index + 2)),
+        position - index as f32,
+    )
+}
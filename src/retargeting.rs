@@ -0,0 +1,258 @@
+//! Retargeting: playing clips authored for one skeleton on another.
+//!
+//! A `RetargetMap` asset, a `.retarget.ron` file, names a source and a target glTF and which
+//! source bone drives which target bone. Once both are loaded, every named animation of the
+//! source is baked into a clip for the target and added to the target's `ClipLibrary` under its
+//! name after the map's `prefix`, so it plays like the target's own clips. Rotations keep their
+//! change from the rest pose in model space, so bones whose rest poses point different ways
+//! still move the same way. Only the root bone keeps its translation, moved from its rest
+//! position by the source's motion scaled by how much longer the target's legs are, so feet
+//! don't slide on a bigger rig. Other bones keep the target's own lengths: scale curves, and
+//! translation curves of any bone but the root, are dropped.
+//!
+//! ```ron
+//! (
+//!     source: "scenes/Walker.glb",
+//!     target: "scenes/Knight.glb",
+//!     prefix: "Walker ",
+//!     root: "mixamorig:Hips",
+//!     leg: ["mixamorig:LeftUpLeg", "mixamorig:LeftLeg", "mixamorig:LeftFoot"],
+//!     bones: {
+//!         "mixamorig:Hips": "pelvis",
+//!         "mixamorig:LeftUpLeg": "thigh_l",
+//!     },
+//! )
+//! ```
+
+use std::collections::BTreeMap;
+use bevy::animation::{AnimationTargetId, Interpolation, Keyframes, VariableCurve};
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
+use bevy::asset::io::Reader;
+use bevy::gltf::{Gltf, GltfNode};
+use bevy::prelude::*;
+use bevy::scene::ron;
+use bevy::utils::HashMap;
+use serde::Deserialize;
+use crate::clip_library::{build_clip_libraries, ClipLibraries};
+
+#[derive(Asset, TypePath, Deserialize, Debug)]
+pub struct RetargetMap {
+    // Asset paths of the glTF the clips come from and of the one they are baked for
+    pub source: String,
+    pub target: String,
+    // Source bone whose translation is kept, usually the hips
+    pub root: String,
+    // Source bones of a leg from the hip down. The root's motion is scaled by how long the
+    // target's leg is next to this one, or not at all when this is empty.
+    #[serde(default)]
+    pub leg: Vec<String>,
+    // Target bone name by source bone name
+    pub bones: BTreeMap<String, String>,
+    // Put before the names of the baked clips, so they can sit next to target clips of the
+    // same name
+    #[serde(default)]
+    pub prefix: String,
+    // Held so the map only counts as loaded once both models are
+    #[serde(skip)]
+    #[dependency]
+    source_model: Handle<Gltf>,
+    #[serde(skip)]
+    #[dependency]
+    target_model: Handle<Gltf>,
+}
+
+#[derive(Default)]
+struct RetargetMapLoader;
+
+impl AssetLoader for RetargetMapLoader {
+    type Asset = RetargetMap;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext<'_>,
+    ) -> Result<RetargetMap, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let mut map: RetargetMap = ron::de::from_bytes(&bytes)?;
+        map.source_model = load_context.load(map.source.clone());
+        map.target_model = load_context.load(map.target.clone());
+        Ok(map)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["retarget.ron"]
+    }
+}
+
+// A node of a model in its rest pose
+struct RestBone {
+    // Names from the top of the node's tree down, which identify its curves in clips
+    path: Vec<Name>,
+    parent: GlobalTransform,
+    world: GlobalTransform,
+}
+
+// Rest pose of every node of a model by name, in the model's space
+fn rest_pose(gltf: &Gltf, nodes: &Assets<GltfNode>) -> HashMap<String, RestBone> {
+    fn visit(node: &GltfNode, path: &[Name], parent: GlobalTransform, bones: &mut HashMap<String, RestBone>) {
+        let path = [path, &[Name::new(node.name.clone())]].concat();
+        let world = parent * GlobalTransform::from(node.transform);
+        for child in &node.children {
+            visit(child, &path, world, bones);
+        }
+        bones.insert(node.name.clone(), RestBone { path, parent, world });
+    }
+
+    let nodes: Vec<&GltfNode> = gltf.nodes.iter().filter_map(|node| nodes.get(node)).collect();
+    let mut bones = HashMap::new();
+    let roots = nodes
+        .iter()
+        .filter(|node| !nodes.iter().any(|parent| parent.children.iter().any(|child| child.index == node.index)));
+    for root in roots {
+        visit(root, &[], GlobalTransform::IDENTITY, &mut bones);
+    }
+    bones
+}
+
+// Length of a chain of bones in the rest pose, if all of them are there
+fn chain_length<'a>(bones: impl IntoIterator<Item = &'a str>, pose: &HashMap<String, RestBone>) -> Option<f32> {
+    let positions: Option<Vec<Vec3>> = bones.into_iter().map(|bone| Some(pose.get(bone)?.world.translation())).collect();
+    let length: f32 = positions?.windows(2).map(|pair| pair[0].distance(pair[1])).sum();
+    (length > f32::EPSILON).then_some(length)
+}
+
+// Rotation part of a transform, as scale doesn't change which way a bone points
+fn rotation(transform: &GlobalTransform) -> Quat {
+    transform.to_scale_rotation_translation().1
+}
+
+// Bakes `clip` from the source's bones onto the target's through the map
+fn retarget_clip(map: &RetargetMap, source: &HashMap<String, RestBone>, target: &HashMap<String, RestBone>, clip: &AnimationClip) -> AnimationClip {
+    let source_leg = chain_length(map.leg.iter().map(String::as_str), source);
+    let target_leg = chain_length(map.leg.iter().filter_map(|bone| map.bones.get(bone)).map(String::as_str), target);
+    let scale = match (source_leg, target_leg) {
+        (Some(source), Some(target)) if map.leg.iter().all(|bone| map.bones.contains_key(bone)) => target / source,
+        _ => 1.0,
+    };
+
+    let mut retargeted = AnimationClip::default();
+    for (from, to) in &map.bones {
+        let (Some(from_bone), Some(to_bone)) = (source.get(from), target.get(to)) else {
+            continue;
+        };
+        let Some(curves) = clip.curves_for_target(AnimationTargetId::from_names(from_bone.path.iter())) else {
+            continue;
+        };
+
+        // The target's parent turns as the source's did from rest, so the bone's own turn is
+        // the source's seen from the target's rest frames:
+        // target = parent_rest⁻¹ · source_parent_rest · source · source_rest⁻¹ · target_rest
+        let before = rotation(&to_bone.parent).inverse() * rotation(&from_bone.parent);
+        let after = rotation(&from_bone.world).inverse() * rotation(&to_bone.world);
+        // The root moves from the target's rest position by the source's motion from its own
+        let (source_parent, target_parent) = (from_bone.parent.affine(), to_bone.parent.affine().inverse());
+        let (source_rest, target_rest) = (from_bone.world.translation(), to_bone.world.translation());
+        let offset = |translation: Vec3| target_parent.transform_vector3(source_parent.transform_vector3(translation) * scale);
+
+        for curve in curves {
+            // Cubic splines store an in tangent, value and out tangent per keyframe, and
+            // tangents only take the linear part of the change
+            let is_value = |index: usize| !matches!(curve.interpolation, Interpolation::CubicSpline) || index % 3 == 1;
+            let keyframes = match &curve.keyframes {
+                Keyframes::Rotation(rotations) => {
+                    Keyframes::Rotation(rotations.iter().map(|rotation| before * *rotation * after).collect())
+                }
+                Keyframes::Translation(translations) if *from == map.root => Keyframes::Translation(
+                    translations
+                        .iter()
+                        .enumerate()
+                        .map(|(index, translation)| {
+                            if is_value(index) {
+                                target_parent.transform_point3(target_rest + (source_parent.transform_point3(*translation) - source_rest) * scale)
+                            } else {
+                                offset(*translation)
+                            }
+                        })
+                        .collect(),
+                ),
+                _ => continue,
+            };
+            retargeted.add_curve_to_target(
+                AnimationTargetId::from_names(to_bone.path.iter()),
+                VariableCurve {
+                    keyframe_timestamps: curve.keyframe_timestamps.clone(),
+                    keyframes,
+                    interpolation: curve.interpolation.clone(),
+                },
+            );
+        }
+    }
+    retargeted
+}
+
+#[allow(clippy::too_many_arguments)]
+fn retarget_clips(
+    mut map_events: EventReader<AssetEvent<RetargetMap>>,
+    mut model_events: EventReader<AssetEvent<Gltf>>,
+    // Maps waiting for the target's clip library
+    mut pending: Local<Vec<AssetId<RetargetMap>>>,
+    maps: Res<Assets<RetargetMap>>,
+    gltfs: Res<Assets<Gltf>>,
+    nodes: Res<Assets<GltfNode>>,
+    mut clips: ResMut<Assets<AnimationClip>>,
+    mut graphs: ResMut<Assets<AnimationGraph>>,
+    mut libraries: ResMut<ClipLibraries>,
+) {
+    for event in map_events.read() {
+        if let AssetEvent::LoadedWithDependencies { id } = event {
+            pending.push(*id);
+        }
+    }
+    // A reloaded model gets a new library, without the clips baked for it before
+    for event in model_events.read() {
+        if let AssetEvent::LoadedWithDependencies { id } = event {
+            pending.extend(maps.iter().filter(|(_, map)| map.target_model.id() == *id).map(|(map, _)| map));
+        }
+    }
+    pending.sort();
+    pending.dedup();
+
+    pending.retain(|id| {
+        let Some(map) = maps.get(*id) else {
+            return false;
+        };
+        let (Some(source), Some(target)) = (gltfs.get(&map.source_model), gltfs.get(&map.target_model)) else {
+            return true;
+        };
+        let Some(library) = libraries.get_mut(&map.target) else {
+            return true;
+        };
+
+        let (source_pose, target_pose) = (rest_pose(source, &nodes), rest_pose(target, &nodes));
+        for (name, clip) in &source.named_animations {
+            let Some(retargeted) = clips.get(clip).map(|clip| retarget_clip(map, &source_pose, &target_pose, clip)) else {
+                continue;
+            };
+            let retargeted = clips.add(retargeted);
+            let name = format!("{}{name}", map.prefix);
+            if !library.add(&name, retargeted, &mut graphs) {
+                warn!("{} already has a clip named {name}, keeping it over the one from {}", map.target, map.source);
+            }
+        }
+        false
+    });
+}
+
+pub struct EzRetargetPlugin;
+
+impl Plugin for EzRetargetPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<RetargetMap>()
+            .init_asset_loader::<RetargetMapLoader>()
+            .add_systems(Update, retarget_clips.after(build_clip_libraries));
+    }
+}
@@ -0,0 +1,284 @@
+//! Root motion: moving an entity by the displacement its clips give the root bone.
+//!
+//! After the clips are sampled, the root bone's movement since the last frame is measured from
+//! the curves of every active clip and blended by the clips' weights in the tangent space of
+//! the transform group, and their poses at the start of the cycle as dual quaternions. That
+//! movement is added to the entity's `Transform` and removed from the bone, which is held at
+//! its position at the start of the cycle. Clips that loop contribute their whole cycle each
+//! time they wrap. Only each frame's movement is added, so when something else places the
+//! entity every frame, like an `InterpolatingComponent` moving it along a path, it stays where
+//! that puts it instead of drifting away by everything gathered since.
+
+use bevy::animation::AnimationTarget;
+use bevy::prelude::*;
+use bevy::reflect::GetPath;
+use bevy::utils::HashMap;
+use crate::animator::PostAnimationSet;
+use crate::clip_sampling::{sample_rotation, sample_translation};
+use crate::transform_blend::{blend_dual_quat, blend_tangent};
+
+// Which part of the root bone's translation moves the entity
+#[derive(Reflect, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum RootTranslation {
+    None,
+    // Only the ground plane, so bobbing and crouching stay on the skeleton
+    #[default]
+    Horizontal,
+    Full,
+}
+
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct RootMotion {
+    // Name of the root bone below the entity
+    pub bone: String,
+    pub translation: RootTranslation,
+    // Also turn the entity with the bone's rotation around the vertical axis
+    pub rotation: bool,
+}
+
+impl Default for RootMotion {
+    fn default() -> Self {
+        RootMotion {
+            bone: "mixamorig:Hips".to_string(),
+            translation: RootTranslation::default(),
+            rotation: false,
+        }
+    }
+}
+
+// Bone found for a `RootMotion` and the playback position of each clip last frame
+#[derive(Component, Default)]
+struct RootMotionState {
+    bone: Option<Entity>,
+    previous: HashMap<AnimationNodeIndex, (f32, u32)>,
+}
+
+// Root bone movement this frame and its pose at the start of the cycle, in the bone's parent space
+struct RootDelta {
+    translation: Vec3,
+    rotation: Quat,
+    reference_translation: Vec3,
+    reference_rotation: Quat,
+}
+
+// Rotation of `q` around the Y axis
+fn yaw(q: Quat) -> Quat {
+    let twist = Quat::from_xyzw(0.0, q.y, 0.0, q.w);
+    if twist.length_squared() <= f32::EPSILON {
+        Quat::IDENTITY
+    } else {
+        twist.normalize()
+    }
+}
+
+// Change of a root channel between two samples, which can be chained and repeated
+trait Displacement: Copy {
+    fn between(from: Self, to: Self) -> Self;
+    fn then(self, next: Self) -> Self;
+    fn times(self, count: u32) -> Self;
+}
+
+impl Displacement for Vec3 {
+    fn between(from: Self, to: Self) -> Self {
+        to - from
+    }
+
+    fn then(self, next: Self) -> Self {
+        self + next
+    }
+
+    fn times(self, count: u32) -> Self {
+        self * count as f32
+    }
+}
+
+impl Displacement for Quat {
+    fn between(from: Self, to: Self) -> Self {
+        to * from.inverse()
+    }
+
+    fn then(self, next: Self) -> Self {
+        next * self
+    }
+
+    fn times(self, count: u32) -> Self {
+        (0..count).fold(Quat::IDENTITY, |total, _| self * total)
+    }
+}
+
+// Change of a curve between two playback positions, given how many times the clip wrapped
+// around in between
+fn cycle_change<T: Displacement>(
+    sample: impl Fn(f32) -> T,
+    (from, to): (f32, f32),
+    wraps: u32,
+    reverse: bool,
+    duration: f32,
+) -> T {
+    if wraps == 0 {
+        return T::between(sample(from), sample(to));
+    }
+    let (start, end) = if reverse { (duration, 0.0) } else { (0.0, duration) };
+    let cycle = T::between(sample(start), sample(end));
+    T::between(sample(from), sample(end))
+        .then(cycle.times(wraps - 1))
+        .then(T::between(sample(start), sample(to)))
+}
+
+fn root_delta(
+    state: &mut RootMotionState,
+    target: &AnimationTarget,
+    player: &AnimationPlayer,
+    graph: &AnimationGraph,
+    clips: &Assets<AnimationClip>,
+) -> Option<RootDelta> {
+    // Each clip's movement this frame and pose at the start of its cycle, with its weight
+    let mut deltas = Vec::new();
+    let mut references = Vec::new();
+    let mut previous = HashMap::default();
+
+    for (node, animation) in player.playing_animations() {
+        let Some(clip) = graph.get(*node).and_then(|node| node.clip.as_ref()).and_then(|clip| clips.get(clip)) else {
+            continue;
+        };
+        let Some(curves) = clip.curves_for_target(target.id) else {
+            continue;
+        };
+        let position = (animation.seek_time(), animation.completions());
+        previous.insert(*node, position);
+
+        let weight = animation.path::<f32>("computed_weight").copied().unwrap_or(animation.weight());
+        if weight <= 0.0 {
+            continue;
+        }
+
+        let duration = clip.duration();
+        let translation = |time| sample_translation(curves, time).unwrap_or_default();
+        let rotation = |time| sample_rotation(curves, time).unwrap_or_default();
+        references.push((Transform::from_translation(translation(0.0)).with_rotation(rotation(0.0)), weight));
+
+        // Clips that just started have not moved yet
+        let Some(&(from, completions)) = state.previous.get(node) else {
+            deltas.push((Transform::IDENTITY, weight));
+            continue;
+        };
+        let wraps = position.1.saturating_sub(completions);
+        let reverse = animation.speed() < 0.0;
+        let span = (from, position.0);
+        let moved = cycle_change(translation, span, wraps, reverse, duration);
+        let turned = cycle_change(rotation, span, wraps, reverse, duration);
+        deltas.push((Transform::from_translation(moved).with_rotation(turned), weight));
+    }
+
+    state.previous = previous;
+    if deltas.is_empty() {
+        return None;
+    }
+    let delta = blend_tangent(&deltas);
+    let reference = blend_dual_quat(&references);
+    Some(RootDelta {
+        translation: delta.translation,
+        rotation: delta.rotation,
+        reference_translation: reference.translation,
+        reference_rotation: reference.rotation,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn apply_root_motion(
+    clips: Res<Assets<AnimationClip>>,
+    graphs: Res<Assets<AnimationGraph>>,
+    mut commands: Commands,
+    mut roots: Query<(Entity, &RootMotion, Option<&mut RootMotionState>)>,
+    children: Query<&Children>,
+    names: Query<&Name>,
+    players: Query<(&AnimationPlayer, &Handle<AnimationGraph>)>,
+    parents: Query<&Parent>,
+    globals: Query<&GlobalTransform>,
+    mut transforms: Query<&mut Transform>,
+    targets: Query<&AnimationTarget>,
+) {
+    for (entity, root_motion, state) in roots.iter_mut() {
+        let Some(mut state) = state else {
+            commands.entity(entity).insert(RootMotionState::default());
+            continue;
+        };
+        if state.bone.is_none() {
+            state.bone = children
+                .iter_descendants(entity)
+                .find(|descendant| names.get(*descendant).is_ok_and(|name| name.as_str() == root_motion.bone));
+        }
+        let Some(bone) = state.bone else {
+            continue;
+        };
+        let (Ok(target), Ok(parent)) = (targets.get(bone), parents.get(bone)) else {
+            continue;
+        };
+        let Ok((player, graph)) = players.get(target.player) else {
+            continue;
+        };
+        let Some(graph) = graphs.get(graph) else {
+            continue;
+        };
+        let Some(delta) = root_delta(&mut state, target, player, graph, &clips) else {
+            continue;
+        };
+
+        // Map from the bone's parent space into the entity's own space
+        let (Ok(model_global), Ok(parent_global)) = (globals.get(entity), globals.get(parent.get())) else {
+            continue;
+        };
+        let to_model = model_global.affine().inverse() * parent_global.affine();
+        let from_model = to_model.inverse();
+        let (_, model_rotation, _) = to_model.to_scale_rotation_translation();
+
+        let mut moved = to_model.transform_vector3(delta.translation);
+        match root_motion.translation {
+            RootTranslation::None => moved = Vec3::ZERO,
+            RootTranslation::Horizontal => moved.y = 0.0,
+            RootTranslation::Full => {}
+        }
+        let turned = if root_motion.rotation {
+            yaw(model_rotation * delta.rotation * model_rotation.inverse())
+        } else {
+            Quat::IDENTITY
+        };
+
+        // Hold the bone at the start of the cycle on the extracted channels
+        if let Ok(mut bone_transform) = transforms.get_mut(bone) {
+            let mut position = to_model.transform_point3(bone_transform.translation);
+            let reference = to_model.transform_point3(delta.reference_translation);
+            match root_motion.translation {
+                RootTranslation::None => {}
+                RootTranslation::Horizontal => {
+                    position.x = reference.x;
+                    position.z = reference.z;
+                }
+                RootTranslation::Full => position = reference,
+            }
+            bone_transform.translation = from_model.transform_point3(position);
+
+            if root_motion.rotation {
+                let rotation = model_rotation * bone_transform.rotation;
+                let reference = yaw(model_rotation * delta.reference_rotation);
+                let held = reference * yaw(rotation).inverse() * rotation;
+                bone_transform.rotation = (model_rotation.inverse() * held).normalize();
+            }
+        }
+
+        if let Ok(mut transform) = transforms.get_mut(entity) {
+            *transform = *transform * Transform::from_translation(moved).with_rotation(turned);
+            transform.rotation = transform.rotation.normalize();
+        }
+    }
+}
+
+pub struct EzRootMotionPlugin;
+
+impl Plugin for EzRootMotionPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<RootMotion>()
+            .add_systems(PostUpdate, apply_root_motion.in_set(PostAnimationSet::Motion));
+    }
+}
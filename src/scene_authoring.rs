@@ -0,0 +1,104 @@
+//! Components that let `.scn.ron` scene files declare animated entities.
+//!
+//! Asset handles can't be written in scene files, so entities name their glTF scene by path
+//! and the handle is loaded when the entity is spawned, or when the path changes on hot reload.
+//! `MaterialTween` carries a colour tween down to every material of the glTF's meshes.
+
+use bevy::prelude::*;
+use crate::GameState;
+use crate::interpolators::InterpolatingComponent;
+use crate::keyframes::KeyframingComponent;
+use crate::sprite_tweens::TweenOutput;
+
+// glTF (or any other) scene to instance under this entity, as an asset path like
+// "scenes/Walker.glb#Scene0"
+#[derive(Component, Reflect, Default, Clone, Debug)]
+#[reflect(Component)]
+pub struct GltfScene {
+    pub path: String,
+}
+
+// Tints every mesh material under the entity with the entity's colour tween, multiplying the
+// material's own base colour. The materials are cloned on first use, so instances of the same
+// glTF can be tinted independently.
+#[derive(Component, Reflect, Default, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct MaterialTween;
+
+// A material that has been made unique to its mesh, with its base colour before tinting
+#[derive(Component)]
+struct TweenedMaterial(LinearRgba);
+
+fn load_gltf_scenes(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    scenes: Query<(Entity, &GltfScene, Option<&Handle<Scene>>, Has<Visibility>), Changed<GltfScene>>,
+) {
+    for (entity, gltf_scene, current, has_visibility) in scenes.iter() {
+        let handle: Handle<Scene> = asset_server.load(&gltf_scene.path);
+        let mut entity = commands.entity(entity);
+        // Replacing the handle respawns the instance, so only do it when the path changed
+        if current != Some(&handle) {
+            entity.insert(handle);
+        }
+        // The rest of `SceneBundle` is computed each frame, so the scene file only needs the
+        // transform, and optionally the visibility
+        entity.insert((GlobalTransform::default(), InheritedVisibility::default(), ViewVisibility::default()));
+        if !has_visibility {
+            entity.insert(Visibility::default());
+        }
+    }
+}
+
+fn update_material_tween_system<C: Component + TweenOutput<Oklaba>>(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    tweens: Query<(Entity, &C), With<MaterialTween>>,
+    children: Query<&Children>,
+    mut meshes: Query<(&mut Handle<StandardMaterial>, Option<&TweenedMaterial>)>,
+) {
+    for (entity, tween) in tweens.iter() {
+        let tint = LinearRgba::from(*tween.output());
+        for descendant in children.iter_descendants(entity) {
+            let Ok((mut handle, tweened)) = meshes.get_mut(descendant) else {
+                continue;
+            };
+            let base = match tweened {
+                Some(tweened) => tweened.0,
+                None => {
+                    let Some(material) = materials.get(&*handle).cloned() else {
+                        continue;
+                    };
+                    let base = material.base_color.to_linear();
+                    *handle = materials.add(material);
+                    commands.entity(descendant).insert(TweenedMaterial(base));
+                    base
+                }
+            };
+            if let Some(material) = materials.get_mut(&*handle) {
+                material.base_color = LinearRgba::new(
+                    base.red * tint.red,
+                    base.green * tint.green,
+                    base.blue * tint.blue,
+                    base.alpha * tint.alpha,
+                )
+                .into();
+            }
+        }
+    }
+}
+
+pub struct EzSceneAuthoringPlugin;
+
+impl Plugin for EzSceneAuthoringPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<GltfScene>()
+            .register_type::<MaterialTween>()
+            .add_systems(Update, load_gltf_scenes)
+            .add_systems(Update,
+                         (
+                             update_material_tween_system::<InterpolatingComponent<Oklaba>>,
+                             update_material_tween_system::<KeyframingComponent<Oklaba>>,
+                         ).run_if(in_state(GameState::Playing)));
+    }
+}
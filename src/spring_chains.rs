@@ -0,0 +1,284 @@
+//! Spring chains: simulated follow-through for tails, antennae and cloth strips.
+//!
+//! The end of each bone in a chain is a point pulled by a spring towards where the finished
+//! pose puts it, relative to the point above it, while gravity and damping act on it and
+//! collision spheres push it out. Bones are then turned, from the top down, to point at the
+//! simulated ends. Points keep the bone lengths the pose has, so stretchy animation still reads.
+//!
+//! The simulation steps in fixed steps of game time, with the pose eased between the last
+//! frame's and this one's, so frame rate and changes to animation speed don't change how a
+//! chain swings, only what it follows. Pausing or slowing game time does the same to chains.
+//!
+//! A chain stops above the first bone that can't be found, and says so once.
+
+use bevy::prelude::*;
+use crate::animator::PostAnimationSet;
+use crate::ik::{find_bone, rotate_in_world, world_transform};
+
+// Length of a simulation step in seconds
+const STEP: f32 = 1.0 / 120.0;
+// Steps a single frame may take; time beyond that after a hitch is dropped
+const MAX_STEPS: usize = 12;
+
+#[derive(Reflect, Clone, Debug)]
+pub struct SpringBone {
+    pub name: String,
+    // Pull towards the posed end of the bone, per second squared
+    pub stiffness: f32,
+    // Slowing of the end relative to the point above it, per second
+    pub damping: f32,
+    // Acceleration on the end of the bone in world space
+    pub gravity: Vec3,
+    // Radius of the end of the bone against collision spheres
+    pub radius: f32,
+}
+
+impl Default for SpringBone {
+    fn default() -> Self {
+        SpringBone {
+            name: String::new(),
+            stiffness: 200.0,
+            damping: 10.0,
+            gravity: Vec3::new(0.0, -9.81, 0.0),
+            radius: 0.02,
+        }
+    }
+}
+
+#[derive(Reflect, Clone, Default, Debug)]
+pub struct SpringCollider {
+    // Bone the sphere moves with, or the model itself when empty
+    pub bone: String,
+    // Centre of the sphere in the bone's space
+    pub offset: Vec3,
+    pub radius: f32,
+}
+
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct SpringChain {
+    // Bones below the model from the top of the chain down. The top is held where the pose has
+    // it and each bone's settings move its end, which is where the next bone starts.
+    pub bones: Vec<SpringBone>,
+    // Length along the last bone's Y axis of its end, or zero to leave the last bone as posed
+    pub tip_length: f32,
+    pub colliders: Vec<SpringCollider>,
+    pub weight: f32,
+}
+
+impl Default for SpringChain {
+    fn default() -> Self {
+        SpringChain {
+            bones: Vec::new(),
+            tip_length: 0.0,
+            colliders: Vec::new(),
+            weight: 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct SpringPoint {
+    position: Vec3,
+    velocity: Vec3,
+}
+
+// Bones found for a `SpringChain` with the names they were looked up by, its simulated points
+// from the top of the chain down, the posed points of the last frame and the time not yet
+// stepped
+#[derive(Component, Default)]
+struct SpringChainState {
+    bones: Vec<Entity>,
+    searched: Vec<String>,
+    points: Vec<SpringPoint>,
+    posed: Vec<Vec3>,
+    remainder: f32,
+}
+
+// Where the pose puts the top of the chain and the end of each bone
+fn posed_points(bones: &[Entity], tip_length: f32, parents: &Query<&Parent>, transforms: &Query<&mut Transform>) -> Vec<Vec3> {
+    let mut points: Vec<Vec3> = bones.iter().map(|bone| world_transform(*bone, parents, transforms).translation()).collect();
+    if let Some(last) = bones.last().filter(|_| tip_length > 0.0) {
+        points.push(world_transform(*last, parents, transforms).transform_point(Vec3::Y * tip_length));
+    }
+    points
+}
+
+fn simulate_spring_chains(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut models: Query<(Entity, &SpringChain, Option<&mut SpringChainState>)>,
+    children: Query<&Children>,
+    names: Query<&Name>,
+    parents: Query<&Parent>,
+    mut transforms: Query<&mut Transform>,
+) {
+    let delta = time.delta_seconds();
+    for (model, chain, state) in models.iter_mut() {
+        let Some(mut state) = state else {
+            commands.entity(model).insert(SpringChainState::default());
+            continue;
+        };
+        // Bones are looked up again when the chain names others, and until the top one turns
+        // up, as the model may not have spawned yet
+        if state.bones.is_empty() || !state.searched.iter().eq(chain.bones.iter().map(|bone| &bone.name)) {
+            state.bones = chain
+                .bones
+                .iter()
+                .map_while(|bone| find_bone(model, &bone.name, &children, &names))
+                .collect();
+            state.searched = chain.bones.iter().map(|bone| bone.name.clone()).collect();
+            state.points.clear();
+            if let Some(missing) = chain.bones.get(state.bones.len()).filter(|_| !state.bones.is_empty()) {
+                warn!("no bone {} below {model}, so its spring chain stops above it", missing.name);
+            }
+        }
+
+        let posed = posed_points(&state.bones, chain.tip_length, &parents, &transforms);
+        if posed.len() < 2 {
+            continue;
+        }
+        if state.points.len() != posed.len() {
+            state.points = posed.iter().map(|position| SpringPoint { position: *position, velocity: Vec3::ZERO }).collect();
+            state.posed = posed.clone();
+            state.remainder = 0.0;
+        }
+
+        let spheres: Vec<(Vec3, f32)> = chain
+            .colliders
+            .iter()
+            .filter_map(|collider| {
+                let bone = if collider.bone.is_empty() { Some(model) } else { find_bone(model, &collider.bone, &children, &names) };
+                Some((world_transform(bone?, &parents, &transforms).transform_point(collider.offset), collider.radius))
+            })
+            .collect();
+
+        // Step k of this frame happens `k * STEP - leftover` seconds into it. A step that
+        // rounding leaves a hair short still counts, or frame times that add up to whole steps
+        // would now and then fall one behind.
+        let state = &mut *state;
+        let leftover = state.remainder;
+        let steps = (((leftover + delta) / STEP + 1e-3) as usize).min(MAX_STEPS);
+        state.remainder = if steps == MAX_STEPS { 0.0 } else { (leftover + delta - steps as f32 * STEP).max(0.0) };
+        let (previous, points) = (&state.posed, &mut state.points);
+        for step in 1..=steps {
+            let along = if delta > 0.0 { ((step as f32 * STEP - leftover) / delta).clamp(0.0, 1.0) } else { 1.0 };
+            let goal = |index: usize| previous[index].lerp(posed[index], along);
+
+            let top = goal(0);
+            let mut above = SpringPoint { position: top, velocity: (top - points[0].position) / STEP };
+            points[0] = above;
+            for (index, point) in points.iter_mut().enumerate().skip(1) {
+                let bone = &chain.bones[index - 1];
+                let offset = goal(index) - goal(index - 1);
+                let start = point.position;
+
+                let pull = (above.position + offset - point.position) * bone.stiffness;
+                let drag = (point.velocity - above.velocity) * bone.damping;
+                point.velocity += (pull - drag + bone.gravity) * STEP;
+                point.position += point.velocity * STEP;
+
+                // Keep the posed length of the bone, then stay out of the spheres
+                let direction = (point.position - above.position).try_normalize().unwrap_or(offset.normalize_or_zero());
+                point.position = above.position + direction * offset.length();
+                for (centre, radius) in &spheres {
+                    let away = point.position - *centre;
+                    let reach = radius + bone.radius;
+                    if away.length_squared() < reach * reach {
+                        point.position = *centre + away.try_normalize().unwrap_or(Vec3::Y) * reach;
+                    }
+                }
+                point.velocity = (point.position - start) / STEP;
+                above = *point;
+            }
+        }
+        state.posed = posed;
+
+        // Turn each bone from the top down so its end lies towards its simulated point. Turning
+        // a bone moves the ones below it, so their posed ends are measured again each time.
+        let weight = chain.weight.clamp(0.0, 1.0);
+        for (index, bone) in state.bones.iter().enumerate() {
+            let posed = posed_points(&state.bones, chain.tip_length, &parents, &transforms);
+            let Some(end) = posed.get(index + 1) else {
+                break;
+            };
+            let start = posed[index];
+            if let (Some(from), Some(to)) = ((*end - start).try_normalize(), (state.points[index + 1].position - start).try_normalize()) {
+                let turn = Quat::IDENTITY.slerp(Quat::from_rotation_arc(from, to), weight);
+                rotate_in_world(*bone, turn, &parents, &mut transforms);
+            }
+        }
+    }
+}
+
+pub struct EzSpringChainPlugin;
+
+impl Plugin for EzSpringChainPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<SpringChain>()
+            .add_systems(PostUpdate, simulate_spring_chains.in_set(PostAnimationSet::Secondary));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::*;
+    use crate::ik::world_transform;
+    use super::{simulate_spring_chains, SpringBone, SpringChain};
+
+    // Tip of a sagging chain on a model moving at a steady speed, after `seconds` stepped in
+    // frames of `frame` seconds. The bones go back to their rest pose each frame, as animation
+    // would put them.
+    fn tip_after(seconds: f32, frame: f32) -> Vec3 {
+        let mut world = World::new();
+        world.insert_resource(Time::<()>::default());
+        let bones = ["top", "middle", "end"];
+        let model = world.spawn((Name::new("Model"), Transform::IDENTITY)).id();
+        let mut parent = model;
+        let mut spawned = Vec::new();
+        for (index, name) in bones.iter().enumerate() {
+            let offset = if index == 0 { Vec3::ZERO } else { Vec3::X * 0.5 };
+            let bone = world.spawn((Name::new(*name), Transform::from_translation(offset))).set_parent(parent).id();
+            spawned.push((bone, offset));
+            parent = bone;
+        }
+        world.entity_mut(model).insert(SpringChain {
+            bones: bones.iter().map(|name| SpringBone { name: name.to_string(), ..default() }).collect(),
+            ..default()
+        });
+
+        // One frame adds the chain's state and the next finds its bones, neither taking time
+        world.run_system_once(simulate_spring_chains);
+        world.run_system_once(simulate_spring_chains);
+        let frames = (seconds / frame).round() as u32;
+        for index in 1..=frames {
+            world.resource_mut::<Time>().advance_by(Duration::from_secs_f32(frame));
+            world.get_mut::<Transform>(model).unwrap().translation = Vec3::Z * index as f32 * frame;
+            for (bone, offset) in &spawned {
+                *world.get_mut::<Transform>(*bone).unwrap() = Transform::from_translation(*offset);
+            }
+            world.run_system_once(simulate_spring_chains);
+        }
+
+        let end = spawned[2].0;
+        let model_position = world.get::<Transform>(model).unwrap().translation;
+        world.run_system_once(move |parents: Query<&Parent>, transforms: Query<&mut Transform>| {
+            world_transform(end, &parents, &transforms).translation()
+        }) - model_position
+    }
+
+    #[test]
+    fn chain_swings_alike_at_any_frame_rate() {
+        let slow = tip_after(0.5, 1.0 / 30.0);
+        let fast = tip_after(0.5, 1.0 / 144.0);
+        // The chain did sag and trail, so there was something to compare
+        assert!(!slow.abs_diff_eq(Vec3::X, 1e-2), "{slow:?}");
+        assert!(slow.abs_diff_eq(fast, 1e-3), "{slow:?} != {fast:?}");
+        // Frames that aren't whole steps leave the same time over when they add up alike
+        let uneven = tip_after(0.52, 1.0 / 50.0);
+        let other = tip_after(0.52, 1.0 / 75.0);
+        assert!(uneven.abs_diff_eq(other, 1e-3), "{uneven:?} != {other:?}");
+    }
+}
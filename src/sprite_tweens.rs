@@ -0,0 +1,73 @@
+//! Writers that let the interpolation and keyframe components drive 2D scenes.
+
+use bevy::math::VectorSpace;
+use bevy::prelude::*;
+use crate::GameState;
+use crate::interpolators::InterpolatingComponent;
+use crate::keyframes::{KeyframeValue, KeyframingComponent};
+use crate::vstransform2d::{AtlasFrame, VSTransform2d};
+
+// Anything that produces a value each frame, so one writer serves both component kinds
+pub trait TweenOutput<T> {
+    fn output(&self) -> &T;
+}
+
+impl<T: VectorSpace + Clone + Send + Sync + 'static> TweenOutput<T> for InterpolatingComponent<T> {
+    fn output(&self) -> &T {
+        &self.current
+    }
+}
+
+impl<T: KeyframeValue> TweenOutput<T> for KeyframingComponent<T> {
+    fn output(&self) -> &T {
+        &self.current
+    }
+}
+
+fn update_transform_2d_system<C: Component + TweenOutput<VSTransform2d>>(
+    mut query: Query<(&mut Transform, &C)>,
+) {
+    for (mut transform, component) in query.iter_mut() {
+        component.output().apply(&mut transform);
+    }
+}
+
+fn update_sprite_color_system<C: Component + TweenOutput<Oklaba>>(
+    mut query: Query<(&mut Sprite, &C)>,
+) {
+    for (mut sprite, component) in query.iter_mut() {
+        sprite.color = (*component.output()).into();
+    }
+}
+
+fn update_atlas_index_system<C: Component + TweenOutput<AtlasFrame>>(
+    layouts: Res<Assets<TextureAtlasLayout>>,
+    mut query: Query<(&mut TextureAtlas, &C)>,
+) {
+    for (mut atlas, component) in query.iter_mut() {
+        let Some(layout) = layouts.get(&atlas.layout) else {
+            continue;
+        };
+        let index = component.output().index(layout.len());
+        if atlas.index != index {
+            atlas.index = index;
+        }
+    }
+}
+
+// Define the SpriteTweenPlugin
+pub struct EzSpriteTweenPlugin;
+
+impl Plugin for EzSpriteTweenPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update,
+                        (
+                            update_transform_2d_system::<InterpolatingComponent<VSTransform2d>>,
+                            update_transform_2d_system::<KeyframingComponent<VSTransform2d>>,
+                            update_sprite_color_system::<InterpolatingComponent<Oklaba>>,
+                            update_sprite_color_system::<KeyframingComponent<Oklaba>>,
+                            update_atlas_index_system::<InterpolatingComponent<AtlasFrame>>,
+                            update_atlas_index_system::<KeyframingComponent<AtlasFrame>>,
+                        ).run_if(in_state(GameState::Playing)));
+    }
+}
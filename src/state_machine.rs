@@ -0,0 +1,476 @@
+//! Data-driven animation state machines on top of `AnimationGraph`.
+//!
+//! A state machine is declared on the root of a model, e.g. in its scene file, and drives every
+//! `AnimationPlayer` below it. Each state plays a clip or a blend space; transitions fire when
+//! all their conditions on the entity's `AnimationParameters` hold, and crossfade over their own
+//! duration. On `Player` entities the `Actions` resource fills in the "speed", "forward" and
+//! "strafe" parameters every frame. With `StrideMatching` on the root, moving states play at the
+//! measured stride rate instead of their authored speed.
+//!
+//! Clips are named as in the model's `ClipLibrary`, and every state and blend-space sample plays
+//! its clip from a node of its own, so states and samples that use the same clip still keep
+//! their own time, speed and weight. Crossfades between states go through
+//! `AnimationTransitions`, which fades the first clip of each state, so single-clip states are
+//! faded by it alone. The clips of a blend space then share out the weight its first clip was
+//! given, by where the parameters are in the space.
+
+use std::time::Duration;
+use bevy::animation::{advance_animations, animate_targets};
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use crate::GameState;
+use crate::actions::Actions;
+use crate::blend_space::{BlendSpace1d, BlendSpace2d};
+use crate::clip_library::ClipLibrary;
+use crate::player::Player;
+use crate::stride_matching::StrideRate;
+
+#[derive(Reflect, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum Comparison {
+    #[default]
+    Greater,
+    Less,
+}
+
+#[derive(Reflect, Clone, Default, Debug)]
+pub struct Condition {
+    pub parameter: String,
+    pub comparison: Comparison,
+    pub value: f32,
+}
+
+impl Condition {
+    fn holds(&self, parameters: Option<&AnimationParameters>) -> bool {
+        let value = parameters.map_or(0.0, |parameters| parameters.get(&self.parameter));
+        match self.comparison {
+            Comparison::Greater => value > self.value,
+            Comparison::Less => value < self.value,
+        }
+    }
+}
+
+// What a state plays
+#[derive(Reflect, Clone, Debug)]
+pub enum Motion {
+    // Name of the clip in the model's `ClipLibrary`, like "Armature|mixamo.com|Layer0"
+    Clip(String),
+    Blend1d(BlendSpace1d),
+    Blend2d(BlendSpace2d),
+}
+
+impl Default for Motion {
+    fn default() -> Self {
+        Motion::Clip(String::new())
+    }
+}
+
+impl Motion {
+    // Clip names with their own playback speeds
+    fn clips(&self) -> Vec<(&str, f32)> {
+        match self {
+            Motion::Clip(clip) => vec![(clip.as_str(), 1.0)],
+            Motion::Blend1d(space) => space.samples.iter().map(|sample| (sample.clip.as_str(), sample.speed)).collect(),
+            Motion::Blend2d(space) => space.samples.iter().map(|sample| (sample.clip.as_str(), sample.speed)).collect(),
+        }
+    }
+
+    fn weights(&self, parameters: Option<&AnimationParameters>) -> Vec<f32> {
+        let get = |name: &str| parameters.map_or(0.0, |parameters| parameters.get(name));
+        match self {
+            Motion::Clip(_) => vec![1.0],
+            Motion::Blend1d(space) => space.weights(get(&space.parameter)),
+            Motion::Blend2d(space) => space.weights(Vec2::new(get(&space.x_parameter), get(&space.y_parameter))),
+        }
+    }
+}
+
+#[derive(Reflect, Clone, Default, Debug)]
+pub struct AnimationState {
+    pub name: String,
+    pub motion: Motion,
+    pub speed: f32,
+    pub looping: bool,
+}
+
+#[derive(Reflect, Clone, Default, Debug)]
+pub struct StateTransition {
+    // State the transition leaves, or any state when `None`
+    pub from: Option<String>,
+    pub to: String,
+    // All of these must hold for the transition to fire
+    pub conditions: Vec<Condition>,
+    // Crossfade duration in seconds
+    pub crossfade: f32,
+}
+
+// Transitions are checked in order and the first one whose conditions hold wins, so more
+// specific transitions should come first
+#[derive(Component, Reflect, Clone, Default, Debug)]
+#[reflect(Component)]
+pub struct AnimationStateMachine {
+    pub states: Vec<AnimationState>,
+    pub transitions: Vec<StateTransition>,
+    pub initial: String,
+}
+
+impl AnimationStateMachine {
+    fn state_index(&self, name: &str) -> Option<usize> {
+        self.states.iter().position(|state| state.name == name)
+    }
+
+    // Names of the clips of every state
+    pub(crate) fn clip_names(&self) -> impl Iterator<Item = &str> {
+        self.states.iter().flat_map(|state| state.motion.clips()).map(|(name, _)| name)
+    }
+
+    // The transition that fires from `current`, if any. A matching transition back into the
+    // current state still wins over later ones, which keeps the machine where it is.
+    fn next_state(&self, current: usize, parameters: Option<&AnimationParameters>) -> Option<(usize, f32)> {
+        let current_name = &self.states[current].name;
+        self.transitions
+            .iter()
+            .filter(|transition| transition.from.as_ref().is_none_or(|from| from == current_name))
+            .find(|transition| transition.conditions.iter().all(|condition| condition.holds(parameters)))
+            .and_then(|transition| Some((self.state_index(&transition.to)?, transition.crossfade)))
+            .filter(|(next, _)| *next != current)
+    }
+}
+
+// Named values read by transition conditions. Missing parameters read as zero.
+#[derive(Component, Reflect, Clone, Default, Debug)]
+#[reflect(Component)]
+pub struct AnimationParameters {
+    pub values: HashMap<String, f32>,
+}
+
+impl AnimationParameters {
+    pub fn get(&self, name: &str) -> f32 {
+        self.values.get(name).copied().unwrap_or_default()
+    }
+
+    pub fn set(&mut self, name: &str, value: f32) {
+        self.values.insert(name.to_string(), value);
+    }
+}
+
+// Graph nodes of one state's clips, the first being the one `AnimationTransitions` fades, and
+// the share of the state's weight each clip had at the last update
+struct StateNodes {
+    clips: Vec<(AnimationNodeIndex, Handle<AnimationClip>)>,
+    shares: Vec<f32>,
+}
+
+impl StateNodes {
+    fn lead(&self) -> Option<AnimationNodeIndex> {
+        self.clips.first().map(|(node, _)| *node)
+    }
+}
+
+// Runtime state of a machine on one `AnimationPlayer`
+#[derive(Component)]
+pub struct ActiveStateMachine {
+    owner: Entity,
+    states: Vec<StateNodes>,
+    current: usize,
+    // States left for the current one whose first clips are still fading out
+    fading: Vec<usize>,
+}
+
+impl ActiveStateMachine {
+    // Normalised cycle phase of the most heavily weighted clip that is playing
+    fn phase(&self, player: &AnimationPlayer, clips: &Assets<AnimationClip>) -> f32 {
+        self.states
+            .iter()
+            .flat_map(|state| &state.clips)
+            .filter_map(|(node, handle)| {
+                let animation = player.animation(*node)?;
+                let duration = clips.get(handle)?.duration();
+                (duration > 0.0).then(|| (animation.weight(), animation.seek_time() / duration))
+            })
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+            .map_or(0.0, |(_, phase)| phase)
+    }
+
+    // Entity with the `AnimationStateMachine` this runs
+    pub(crate) fn owner(&self) -> Entity {
+        self.owner
+    }
+
+    // Name of the current state in `machine`, the one this was started from
+    pub(crate) fn current_state<'a>(&self, machine: &'a AnimationStateMachine) -> Option<&'a str> {
+        machine.states.get(self.current).map(|state| state.name.as_str())
+    }
+
+    // Nodes and clips of the current state, in the order of its motion
+    pub(crate) fn current_clips(&self) -> &[(AnimationNodeIndex, Handle<AnimationClip>)] {
+        self.states.get(self.current).map_or(&[], |state| &state.clips)
+    }
+
+    // Switches to the state named `name` at once, stopping the clips of every other state, and
+    // returns its nodes and clips, or `None` if the machine has no such state
+    pub(crate) fn jump_to_state(
+        &mut self,
+        machine: &AnimationStateMachine,
+        name: &str,
+        transitions: &mut AnimationTransitions,
+        player: &mut AnimationPlayer,
+    ) -> Option<&[(AnimationNodeIndex, Handle<AnimationClip>)]> {
+        let next = machine.state_index(name).filter(|next| *next < self.states.len())?;
+        for (node, _) in self.states.iter().flat_map(|state| &state.clips) {
+            player.stop(*node);
+        }
+        self.current = next;
+        self.fading.clear();
+        // With its old main animation stopped, the transitions have nothing to fade out
+        if let Some(lead) = self.states[next].lead() {
+            let animation = transitions.play(player, lead, Duration::ZERO);
+            if machine.states[next].looping {
+                animation.repeat();
+            }
+        }
+        Some(&self.states[next].clips)
+    }
+
+    // Nodes and clips of the current state and of those still fading out
+    pub(crate) fn active_clips(&self) -> impl Iterator<Item = &(AnimationNodeIndex, Handle<AnimationClip>)> {
+        std::iter::once(self.current)
+            .chain(self.fading.iter().copied())
+            .filter_map(|state| self.states.get(state))
+            .flat_map(|state| &state.clips)
+    }
+}
+
+// Adds a node to `graph` for every clip of every state, found in `library`, and starts the
+// initial state. Every clip must be in the library.
+pub(crate) fn start_state_machine(
+    owner: Entity,
+    machine: &AnimationStateMachine,
+    library: &ClipLibrary,
+    graph: &mut AnimationGraph,
+    player: &mut AnimationPlayer,
+) -> (ActiveStateMachine, AnimationTransitions) {
+    let states: Vec<StateNodes> = machine
+        .states
+        .iter()
+        .map(|state| {
+            let clips = state
+                .motion
+                .clips()
+                .into_iter()
+                .filter_map(|(name, _)| {
+                    let clip = library.clip(name)?.clone();
+                    Some((graph.add_clip(clip.clone(), 1.0, graph.root), clip))
+                })
+                .collect();
+            StateNodes { clips, shares: Vec::new() }
+        })
+        .collect();
+    let current = machine.state_index(&machine.initial).unwrap_or_default();
+    let mut transitions = AnimationTransitions::new();
+    if let Some(lead) = states.get(current).and_then(StateNodes::lead) {
+        let animation = transitions.play(player, lead, Duration::ZERO);
+        if machine.states[current].looping {
+            animation.repeat();
+        }
+    }
+    (ActiveStateMachine { owner, states, current, fading: Vec::new() }, transitions)
+}
+
+// Without input, a player that something else moves, like its path, takes its parameters from
+// the measured movement, a cycle per second reading as full speed
+fn set_action_parameters(
+    actions: Res<Actions>,
+    mut query: Query<(&mut AnimationParameters, Option<&StrideRate>), With<Player>>,
+) {
+    for (mut parameters, stride_rate) in query.iter_mut() {
+        let movement = match (actions.player_movement, stride_rate) {
+            (Some(movement), _) => movement,
+            (None, Some(stride_rate)) => Vec2::new(0.0, stride_rate.pace().clamp(-1.0, 1.0)),
+            (None, None) => Vec2::ZERO,
+        };
+        parameters.set("speed", movement.length());
+        parameters.set("forward", movement.y);
+        parameters.set("strafe", movement.x);
+    }
+}
+
+fn update_state_machines(
+    clips: Res<Assets<AnimationClip>>,
+    machines: Query<(&AnimationStateMachine, Option<&AnimationParameters>, Option<&StrideRate>)>,
+    mut players: Query<(&mut ActiveStateMachine, &mut AnimationTransitions, &mut AnimationPlayer)>,
+) {
+    for (mut active, mut transitions, mut player) in players.iter_mut() {
+        let Ok((machine, parameters, stride_rate)) = machines.get(active.owner) else {
+            continue;
+        };
+        // Clips that start playing join the cycle at the phase of the dominant clip
+        let phase = active.phase(&player, &clips);
+
+        // States added since the graph was built, e.g. by a hot reload, have no nodes yet
+        let state_count = active.states.len().min(machine.states.len());
+        if active.current < state_count {
+            if let Some((next, crossfade)) = machine.next_state(active.current, parameters) {
+                if next < state_count {
+                    let looping = machine.states[next].looping;
+                    enter_state(&mut active, &mut transitions, &mut player, &clips, (next, crossfade), phase, looping);
+                }
+            }
+        }
+
+        let active = &mut *active;
+        let stride_rate = stride_rate.map(StrideRate::cycles_per_second);
+        for (index, (nodes, state)) in active.states.iter_mut().zip(&machine.states).enumerate() {
+            if index == active.current || active.fading.contains(&index) {
+                play_motion(&mut player, &clips, nodes, state, parameters, phase, stride_rate);
+            }
+        }
+    }
+}
+
+// Crossfades to the state `next` over `crossfade` seconds, its first clip joining the cycle at
+// `phase` if it isn't playing yet
+fn enter_state(
+    active: &mut ActiveStateMachine,
+    transitions: &mut AnimationTransitions,
+    player: &mut AnimationPlayer,
+    clips: &Assets<AnimationClip>,
+    (next, crossfade): (usize, f32),
+    phase: f32,
+    looping: bool,
+) {
+    let previous = active.current;
+    active.current = next;
+    active.fading.retain(|state| *state != next && *state != previous);
+    active.fading.push(previous);
+    let Some((lead, clip)) = active.states[next].clips.first() else {
+        return;
+    };
+    // The state's first clip leads already, so there is nothing to fade
+    if transitions.get_main_animation() == Some(*lead) {
+        return;
+    }
+
+    let starting = player.animation(*lead).is_none();
+    let animation = transitions.play(player, *lead, Duration::from_secs_f32(crossfade.max(0.0)));
+    if starting {
+        if let Some(clip) = clips.get(clip) {
+            animation.seek_to(phase * clip.duration());
+        }
+        if looping {
+            animation.repeat();
+        }
+    }
+}
+
+// Shares out the weight `AnimationTransitions` just gave the first clip of each state among
+// the state's clips. Clips of no state with weight left stop.
+fn share_state_weights(mut players: Query<(&mut ActiveStateMachine, &mut AnimationPlayer)>) {
+    for (mut active, mut player) in players.iter_mut() {
+        let active = &mut *active;
+        let (states, current) = (&active.states, active.current);
+        let lead_weight = |state: usize| Some(player.animation(states[state].lead()?)?.weight());
+
+        let mut weighted = vec![(current, lead_weight(current).unwrap_or(1.0))];
+        active.fading.retain(|state| match lead_weight(*state) {
+            Some(weight) if weight > 0.0 => {
+                weighted.push((*state, weight));
+                true
+            }
+            _ => false,
+        });
+
+        let mut node_weights: HashMap<AnimationNodeIndex, f32> = states
+            .iter()
+            .flat_map(|state| &state.clips)
+            .map(|(node, _)| (*node, 0.0))
+            .collect();
+        for (state, weight) in &weighted {
+            let nodes = &states[*state];
+            for ((node, _), share) in nodes.clips.iter().zip(&nodes.shares) {
+                *node_weights.entry(*node).or_default() += weight * share;
+            }
+        }
+        // First clips left at zero are still the transitions' to fade or stop
+        let leads: Vec<AnimationNodeIndex> = weighted.iter().filter_map(|(state, _)| states[*state].lead()).collect();
+        for (node, weight) in node_weights {
+            if weight > 0.0 || leads.contains(&node) {
+                if let Some(animation) = player.animation_mut(node) {
+                    animation.set_weight(weight);
+                }
+            } else {
+                player.stop(node);
+            }
+        }
+    }
+}
+
+// Plays a state's clips and keeps their blend weights for `share_state_weights`. Every clip
+// plays at the speed that makes it go through its cycle at the same rate, the weighted mean of
+// the clips' own rates, so their phases stay matched and feet land together. A stride rate
+// replaces that rate in states that move, and its sign plays them backwards when the entity
+// moves backwards.
+fn play_motion(
+    player: &mut AnimationPlayer,
+    clips: &Assets<AnimationClip>,
+    nodes: &mut StateNodes,
+    state: &AnimationState,
+    parameters: Option<&AnimationParameters>,
+    phase: f32,
+    stride_rate: Option<f32>,
+) {
+    let weights = state.motion.weights(parameters);
+    let speeds = state.motion.clips();
+    let durations: Vec<Option<f32>> = nodes
+        .clips
+        .iter()
+        .map(|(_, handle)| clips.get(handle).map(AnimationClip::duration).filter(|duration| *duration > 0.0))
+        .collect();
+
+    // Cycles per second of the blend
+    let rate: f32 = weights
+        .iter()
+        .zip(&speeds)
+        .zip(&durations)
+        .filter_map(|((weight, (_, speed)), duration)| Some(weight * speed / (*duration)?))
+        .sum::<f32>()
+        * state.speed;
+    let rate = match stride_rate {
+        Some(stride_rate) if rate != 0.0 => stride_rate,
+        _ => rate,
+    };
+
+    for (((node, _), duration), weight) in nodes.clips.iter().zip(&durations).zip(&weights) {
+        let Some(duration) = *duration else {
+            continue;
+        };
+        if player.animation(*node).is_none() && *weight > 0.0 {
+            let animation = player.start(*node);
+            animation.set_weight(0.0);
+            animation.seek_to(phase * duration);
+            if state.looping {
+                animation.repeat();
+            }
+        }
+        if let Some(animation) = player.animation_mut(*node) {
+            animation.set_speed(rate * duration);
+        }
+    }
+    nodes.shares = weights;
+}
+
+pub struct EzStateMachinePlugin;
+
+impl Plugin for EzStateMachinePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<AnimationStateMachine>()
+            .register_type::<AnimationParameters>()
+            .add_systems(Update,
+                         (
+                             set_action_parameters,
+                             update_state_machines,
+                         ).chain().run_if(in_state(GameState::Playing)))
+            .add_systems(PostUpdate, share_state_weights
+                .after(advance_animations)
+                .before(animate_targets));
+    }
+}
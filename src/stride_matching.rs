@@ -0,0 +1,126 @@
+//! Stride matching: playing locomotion clips at the rate the entity actually moves.
+//!
+//! The entity's ground speed is measured from how far its `GlobalTransform` moved along the way
+//! it faces since the last frame, whatever moved it. Divided by the distance one cycle of the
+//! clip covers, that gives the cycles per second at which the feet stay planted, negative while
+//! the entity moves backwards so the clips play in reverse.
+
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+use crate::GameState;
+use crate::state_machine::ActiveStateMachine;
+
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct StrideMatching {
+    // Ground distance covered by one cycle of the clip, in world units
+    pub stride_length: f32,
+    // Limits on the playback rate, in cycles per second
+    pub min_rate: f32,
+    pub max_rate: f32,
+    // Seconds for the rate to close most of the gap to the measured speed, zero to follow it at once
+    pub smoothing: f32,
+    // Direction the model faces in the entity's space, +Z for glTF models
+    pub forward: Vec3,
+}
+
+impl Default for StrideMatching {
+    fn default() -> Self {
+        StrideMatching {
+            stride_length: 1.0,
+            min_rate: 0.0,
+            max_rate: 2.0,
+            smoothing: 0.15,
+            forward: Vec3::Z,
+        }
+    }
+}
+
+// Measured playback rate of a `StrideMatching` entity, negative when moving backwards
+#[derive(Component, Default)]
+pub struct StrideRate {
+    previous: Option<Vec3>,
+    rate: f32,
+    pace: f32,
+}
+
+impl StrideRate {
+    pub fn cycles_per_second(&self) -> f32 {
+        self.rate
+    }
+
+    // Smoothed cycles per second the movement calls for, before the rate limits
+    pub fn pace(&self) -> f32 {
+        self.pace
+    }
+}
+
+// Runs once transforms are final, so the rate is ready for the next frame's animation systems
+fn measure_stride_rate(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut query: Query<(Entity, &StrideMatching, &GlobalTransform, Option<&mut StrideRate>)>,
+) {
+    let delta = time.delta_seconds();
+    for (entity, stride, transform, rate) in query.iter_mut() {
+        let position = transform.translation();
+        let Some(mut rate) = rate else {
+            commands.entity(entity).insert(StrideRate { previous: Some(position), rate: stride.min_rate, pace: 0.0 });
+            continue;
+        };
+        let Some(previous) = rate.previous.replace(position) else {
+            continue;
+        };
+        if delta <= 0.0 || stride.stride_length <= 0.0 {
+            continue;
+        }
+
+        let forward = transform.affine().transform_vector3(stride.forward).normalize_or_zero();
+        let ground_speed = (position - previous).dot(forward) / delta;
+        let pace = ground_speed / stride.stride_length;
+        let target = pace.abs().clamp(stride.min_rate, stride.max_rate.max(stride.min_rate));
+        let target = target.copysign(ground_speed);
+        let blend = if stride.smoothing > 0.0 { 1.0 - (-delta / stride.smoothing).exp() } else { 1.0 };
+        rate.rate += (target - rate.rate) * blend;
+        rate.pace += (pace - rate.pace) * blend;
+    }
+}
+
+// Players that just loop their clips, without a state machine, play every clip at the rate.
+// Clips without weight, like those of layers, keep their own speed.
+fn apply_stride_rate(
+    clips: Res<Assets<AnimationClip>>,
+    graphs: Res<Assets<AnimationGraph>>,
+    rates: Query<&StrideRate>,
+    parents: Query<&Parent>,
+    mut players: Query<(Entity, &mut AnimationPlayer, &Handle<AnimationGraph>), (With<AnimationTransitions>, Without<ActiveStateMachine>)>,
+) {
+    for (entity, mut player, graph) in players.iter_mut() {
+        let Some(rate) = parents.iter_ancestors(entity).find_map(|ancestor| rates.get(ancestor).ok()) else {
+            continue;
+        };
+        let Some(graph) = graphs.get(graph) else {
+            continue;
+        };
+        for (node, animation) in player.playing_animations_mut().filter(|(_, animation)| animation.weight() > 0.0) {
+            let duration = graph
+                .get(*node)
+                .and_then(|node| node.clip.as_ref())
+                .and_then(|clip| clips.get(clip))
+                .map(AnimationClip::duration);
+            if let Some(duration) = duration {
+                animation.set_speed(rate.cycles_per_second() * duration);
+            }
+        }
+    }
+}
+
+pub struct EzStrideMatchingPlugin;
+
+impl Plugin for EzStrideMatchingPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<StrideMatching>()
+            .add_systems(Update, apply_stride_rate.run_if(in_state(GameState::Playing)))
+            .add_systems(PostUpdate, measure_stride_rate.after(TransformSystem::TransformPropagate));
+    }
+}
@@ -0,0 +1,110 @@
+//! Clocks behind the interpolation factor.
+//!
+//! Samplers used to read `Time::elapsed_seconds` directly, so the phase of every cycle depended
+//! on how long the app had been running. A timeline keeps its own position instead: it only
+//! advances while playing, and it can be saved, restored and authored in scenes.
+
+use std::f32::consts::TAU;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::GameState;
+use crate::interpolators::interpolation_factor_at;
+
+// Period of `interpolation_factor_at`, after which the cycle repeats
+const PERIOD: f32 = TAU;
+
+#[derive(Reflect, Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub enum TimelineDirection {
+    #[default]
+    Forward,
+    Reverse,
+}
+
+impl TimelineDirection {
+    fn sign(self) -> f32 {
+        match self {
+            TimelineDirection::Forward => 1.0,
+            TimelineDirection::Reverse => -1.0,
+        }
+    }
+}
+
+// Position along the interpolation cycle. `elapsed` is kept within one period and `loops`
+// counts the periods completed, in either direction. Entities with their own timeline follow
+// it; everything else follows the shared `Timeline` resource.
+#[derive(Component, Resource, Reflect, Clone, Debug, Serialize, Deserialize)]
+#[reflect(Component, Resource, Serialize, Deserialize)]
+pub struct Timeline {
+    pub elapsed: f32,
+    pub direction: TimelineDirection,
+    pub loops: u32,
+    pub speed: f32,
+    pub(crate) value: f32,
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Timeline {
+            elapsed: 0.0,
+            direction: TimelineDirection::Forward,
+            loops: 0,
+            speed: 1.0,
+            value: interpolation_factor_at(0.0),
+        }
+    }
+}
+
+impl Timeline {
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+
+    // Position within the current period, from 0 to 1
+    pub fn cycle(&self) -> f32 {
+        self.elapsed / PERIOD
+    }
+
+    // Value the timeline will have after another `seconds` of playback, without advancing it
+    pub fn value_after(&self, seconds: f32) -> f32 {
+        interpolation_factor_at(self.elapsed + seconds * self.signed_speed())
+    }
+
+    pub fn advance(&mut self, seconds: f32) {
+        self.elapsed += seconds * self.signed_speed();
+        while self.elapsed >= PERIOD {
+            self.elapsed -= PERIOD;
+            self.loops += 1;
+        }
+        while self.elapsed < 0.0 {
+            self.elapsed += PERIOD;
+            self.loops += 1;
+        }
+        self.value = interpolation_factor_at(self.elapsed);
+    }
+
+    fn signed_speed(&self) -> f32 {
+        self.speed * self.direction.sign()
+    }
+}
+
+pub(crate) fn advance_timelines(
+    time: Res<Time>,
+    mut shared: ResMut<Timeline>,
+    mut timelines: Query<&mut Timeline>,
+) {
+    shared.advance(time.delta_seconds());
+    for mut timeline in timelines.iter_mut() {
+        timeline.advance(time.delta_seconds());
+    }
+}
+
+pub struct EzTimelinePlugin;
+
+impl Plugin for EzTimelinePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Timeline>()
+            .register_type::<Timeline>()
+            .add_systems(Update, advance_timelines.run_if(in_state(GameState::Playing)));
+    }
+}
@@ -0,0 +1,319 @@
+//! Blending of transforms in the tangent space of the transform group, or with dual quaternions.
+//!
+//! A `Transform` is not a vector space: rotations compose by multiplication and scales by
+//! products, so summing and scaling their components gives meaningless results. Here the
+//! rigid part is mapped to its se(3) twist with the log map and the scale to its logarithm.
+//! Those coordinates form a real vector space, and the exp map takes any combination of them
+//! back to a valid transform.
+
+use std::ops::{Add, Div, Mul, Neg, Sub};
+use bevy::math::VectorSpace;
+use bevy::prelude::*;
+
+// Angles below which the series expansions of the se(3) coefficients are used
+const SMALL_ANGLE: f32 = 1e-4;
+
+// Coordinates of a transform in the tangent space at the identity. `angular` and `linear` are
+// the se(3) twist of the rotation and translation, `scale` coordinates of the scale,
+// which `log` and `exp` take to be its natural log.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Reflect)]
+pub struct TransformTangent {
+    pub angular: Vec3,
+    pub linear: Vec3,
+    pub scale: Vec3,
+}
+
+impl TransformTangent {
+    pub fn log(transform: &Transform) -> Self {
+        // Take the rotation in the positive hemisphere so the angle lies in [0, pi]
+        let rotation = if transform.rotation.w < 0.0 {
+            -transform.rotation
+        } else {
+            transform.rotation
+        };
+        let (axis, angle) = rotation.to_axis_angle();
+        let angular = if angle.abs() <= f32::EPSILON { Vec3::ZERO } else { axis * angle };
+
+        TransformTangent {
+            angular,
+            linear: inverse_left_jacobian(angular, transform.translation),
+            scale: log_scale(transform.scale),
+        }
+    }
+
+    pub fn exp(self) -> Transform {
+        let angle = self.angular.length();
+        let rotation = if angle <= f32::EPSILON {
+            Quat::IDENTITY
+        } else {
+            Quat::from_axis_angle(self.angular / angle, angle)
+        };
+
+        Transform {
+            translation: left_jacobian(self.angular, self.linear),
+            rotation,
+            scale: exp_scale(self.scale),
+        }
+    }
+}
+
+fn log_scale(scale: Vec3) -> Vec3 {
+    Vec3::new(scale.x.ln(), scale.y.ln(), scale.z.ln())
+}
+
+fn exp_scale(log_scale: Vec3) -> Vec3 {
+    Vec3::new(log_scale.x.exp(), log_scale.y.exp(), log_scale.z.exp())
+}
+
+// V(w) * v, which maps twist coordinates to the translation of the exponential
+fn left_jacobian(w: Vec3, v: Vec3) -> Vec3 {
+    let theta = w.length();
+    let (a, b) = if theta < SMALL_ANGLE {
+        (0.5, 1.0 / 6.0)
+    } else {
+        let theta2 = theta * theta;
+        ((1.0 - theta.cos()) / theta2, (theta - theta.sin()) / (theta2 * theta))
+    };
+    let wv = w.cross(v);
+    v + wv * a + w.cross(wv) * b
+}
+
+// V(w)^-1 * t, the inverse of `left_jacobian`
+fn inverse_left_jacobian(w: Vec3, t: Vec3) -> Vec3 {
+    let theta = w.length();
+    let c = if theta < SMALL_ANGLE {
+        1.0 / 12.0
+    } else {
+        (1.0 - theta * theta.sin() / (2.0 * (1.0 - theta.cos()))) / (theta * theta)
+    };
+    let wt = w.cross(t);
+    t - wt * 0.5 + w.cross(wt) * c
+}
+
+impl Add for TransformTangent {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        TransformTangent {
+            angular: self.angular + rhs.angular,
+            linear: self.linear + rhs.linear,
+            scale: self.scale + rhs.scale,
+        }
+    }
+}
+
+impl Sub for TransformTangent {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        self + -rhs
+    }
+}
+
+impl Neg for TransformTangent {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        self * -1.0
+    }
+}
+
+impl Mul<f32> for TransformTangent {
+    type Output = Self;
+
+    fn mul(self, rhs: f32) -> Self::Output {
+        TransformTangent {
+            angular: self.angular * rhs,
+            linear: self.linear * rhs,
+            scale: self.scale * rhs,
+        }
+    }
+}
+
+impl Div<f32> for TransformTangent {
+    type Output = Self;
+
+    fn div(self, rhs: f32) -> Self::Output {
+        TransformTangent {
+            angular: self.angular / rhs,
+            linear: self.linear / rhs,
+            scale: self.scale / rhs,
+        }
+    }
+}
+
+impl VectorSpace for TransformTangent {
+    const ZERO: Self = TransformTangent {
+        angular: Vec3::ZERO,
+        linear: Vec3::ZERO,
+        scale: Vec3::ZERO,
+    };
+}
+
+// Unit dual quaternion for the rigid part of a transform
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DualQuat {
+    pub real: Quat,
+    pub dual: Quat,
+}
+
+impl DualQuat {
+    pub fn from_rigid(rotation: Quat, translation: Vec3) -> Self {
+        let translation = Quat::from_xyzw(translation.x, translation.y, translation.z, 0.0);
+        DualQuat {
+            real: rotation,
+            dual: translation * rotation * 0.5,
+        }
+    }
+
+    pub fn rotation(&self) -> Quat {
+        self.real
+    }
+
+    pub fn translation(&self) -> Vec3 {
+        let translation = self.dual * self.real.conjugate() * 2.0;
+        Vec3::new(translation.x, translation.y, translation.z)
+    }
+
+    // Projects back onto unit dual quaternions after a weighted sum
+    pub fn normalize(self) -> Self {
+        let norm = self.real.length();
+        if norm <= f32::EPSILON {
+            return DualQuat::from_rigid(Quat::IDENTITY, Vec3::ZERO);
+        }
+        let real = self.real / norm;
+        let dual = self.dual / norm;
+        DualQuat {
+            real,
+            dual: dual - real * real.dot(dual),
+        }
+    }
+}
+
+// Normalised weights, falling back to the first entry when they sum to zero
+fn normalized_weights(weighted: &[(Transform, f32)]) -> Vec<f32> {
+    let total: f32 = weighted.iter().map(|(_, weight)| weight).sum();
+    if total.abs() <= f32::EPSILON {
+        return (0..weighted.len()).map(|i| if i == 0 { 1.0 } else { 0.0 }).collect();
+    }
+    weighted.iter().map(|(_, weight)| weight / total).collect()
+}
+
+// Weighted geometric mean of the scales
+fn blend_scale(weighted: &[(Transform, f32)], weights: &[f32]) -> Vec3 {
+    exp_scale(
+        weighted
+            .iter()
+            .zip(weights)
+            .fold(Vec3::ZERO, |sum, ((transform, _), weight)| {
+                sum + log_scale(transform.scale) * *weight
+            }),
+    )
+}
+
+// Blends transforms by averaging their offsets from the most heavily weighted one in the
+// tangent space, which stays accurate for rotations far from the identity
+pub fn blend_tangent(weighted: &[(Transform, f32)]) -> Transform {
+    let Some((reference, _)) = weighted.iter().max_by(|a, b| a.1.total_cmp(&b.1)) else {
+        return Transform::IDENTITY;
+    };
+    let weights = normalized_weights(weighted);
+    let inverse_rotation = reference.rotation.inverse();
+
+    let offset = weighted
+        .iter()
+        .zip(&weights)
+        .fold(TransformTangent::ZERO, |sum, ((transform, _), weight)| {
+            let relative = Transform {
+                translation: inverse_rotation * (transform.translation - reference.translation),
+                rotation: inverse_rotation * transform.rotation,
+                scale: Vec3::ONE,
+            };
+            sum + TransformTangent::log(&relative) * *weight
+        })
+        .exp();
+
+    Transform {
+        translation: reference.translation + reference.rotation * offset.translation,
+        rotation: (reference.rotation * offset.rotation).normalize(),
+        scale: blend_scale(weighted, &weights),
+    }
+}
+
+// Dual quaternion linear blending of the rigid parts, with scales blended in log space
+pub fn blend_dual_quat(weighted: &[(Transform, f32)]) -> Transform {
+    let Some((first, _)) = weighted.first() else {
+        return Transform::IDENTITY;
+    };
+    let weights = normalized_weights(weighted);
+
+    let mut sum = DualQuat {
+        real: Quat::from_xyzw(0.0, 0.0, 0.0, 0.0),
+        dual: Quat::from_xyzw(0.0, 0.0, 0.0, 0.0),
+    };
+    for ((transform, _), weight) in weighted.iter().zip(&weights) {
+        let mut dq = DualQuat::from_rigid(transform.rotation, transform.translation);
+        // Keep every rotation in the hemisphere of the first, so antipodal quaternions don't cancel
+        if dq.real.dot(first.rotation) < 0.0 {
+            dq.real = -dq.real;
+            dq.dual = -dq.dual;
+        }
+        sum.real = sum.real + dq.real * *weight;
+        sum.dual = sum.dual + dq.dual * *weight;
+    }
+    let blended = sum.normalize();
+
+    Transform {
+        translation: blended.translation(),
+        rotation: blended.rotation(),
+        scale: blend_scale(weighted, &weights),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::{Quat, Transform, Vec3};
+    use super::{blend_dual_quat, blend_tangent};
+
+    #[test]
+    fn dual_quat_blend_of_antipodal_rotations() {
+        // q and -q are the same rotation, and must not sum to nothing
+        let rotation = Quat::from_rotation_y(1.0);
+        let a = Transform::from_translation(Vec3::new(1.0, 2.0, 3.0)).with_rotation(rotation);
+        let b = a.with_rotation(-rotation);
+        let blended = blend_dual_quat(&[(a, 0.5), (b, 0.5)]);
+        assert!(blended.rotation.dot(rotation).abs() > 1.0 - 1e-5, "{blended:?}");
+        assert!(blended.translation.abs_diff_eq(a.translation, 1e-4), "{blended:?}");
+    }
+
+    #[test]
+    fn dual_quat_blend_of_rigid_transforms() {
+        let a = Transform::from_translation(Vec3::X).with_rotation(Quat::from_rotation_z(0.4));
+        let b = Transform::from_translation(Vec3::Y).with_rotation(Quat::from_rotation_z(1.2));
+        let blended = blend_dual_quat(&[(a, 1.0), (b, 1.0)]);
+        let expected = Quat::from_rotation_z(0.8);
+        assert!(blended.rotation.dot(expected).abs() > 1.0 - 1e-5, "{blended:?}");
+        assert!(blended.rotation.is_normalized());
+        // Weights are normalised, so a single weighted transform comes back as it is
+        let single = blend_dual_quat(&[(b, 3.0)]);
+        assert!(single.translation.abs_diff_eq(b.translation, 1e-4), "{single:?}");
+    }
+
+    #[test]
+    fn tangent_blend_of_rotations_is_their_slerp_midpoint() {
+        let axis = Vec3::new(1.0, 1.0, 0.0).normalize();
+        for (from, to) in [(0.0, 1.0), (0.3, 2.9), (-2.5, 2.5)] {
+            let a = Quat::from_axis_angle(axis, from);
+            let b = Quat::from_axis_angle(axis, to);
+            let blended = blend_tangent(&[(Transform::from_rotation(a), 0.5), (Transform::from_rotation(b), 0.5)]);
+            let expected = a.slerp(b, 0.5);
+            assert!(blended.rotation.dot(expected).abs() > 1.0 - 1e-5, "{from} {to}: {blended:?} != {expected:?}");
+        }
+        // Translations and scales blend with the rotations, scales geometrically
+        let a = Transform::from_translation(Vec3::X).with_scale(Vec3::splat(2.0));
+        let b = Transform::from_translation(Vec3::Z).with_scale(Vec3::splat(0.5));
+        let blended = blend_tangent(&[(a, 1.0), (b, 1.0)]);
+        assert!(blended.translation.abs_diff_eq(Vec3::new(0.5, 0.0, 0.5), 1e-4), "{blended:?}");
+        assert!(blended.scale.abs_diff_eq(Vec3::ONE, 1e-4), "{blended:?}");
+    }
+}
@@ -1,23 +1,225 @@
 use std::any::Any;
+use std::f32::consts::LN_2;
 use std::fmt::{Debug, Formatter};
 use std::ops::{Add, Div, Mul, Neg, Sub};
-use bevy::math::{Vec3, VectorSpace};
-use bevy::prelude::{FromReflect, Reflect, Transform, TypePath};
+use bevy::math::{Quat, Vec3, VectorSpace};
+use bevy::prelude::{FromReflect, Reflect, ReflectDeserialize, ReflectSerialize, Transform, TypePath};
 use bevy::reflect::{ApplyError, DynamicStruct, FieldIter, GetTypeRegistration, ReflectMut, ReflectOwned, ReflectRef, Struct, Typed, TypeInfo, TypeRegistration};
+use serde::{Deserialize, Serialize};
+use crate::transform_blend::TransformTangent;
 
-// Wrapper type for Transform
-#[derive(Clone, Reflect)]
-pub struct VSTransform(pub(crate) Transform);
+// How the scale moves when interpolating between two transforms
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Reflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub enum ScaleInterpolation {
+    // Constant ratio per unit of t, which reads as even growth. Components that change sign or
+    // start or end at zero have no exponential path, so they fall back to linear. Splines take
+    // the log of the scale's size and carry its sign alongside, so mirrored keys stay mirrored,
+    // and a component whose keys change sign flips where their signs balance out.
+    #[default]
+    Exponential,
+    Linear,
+    // Interpolates sign(s) * log2(1 + |s|), which is exponential-like for large scales but
+    // passes smoothly through zero, so objects can mirror or collapse
+    SignedLog,
+}
+
+impl ScaleInterpolation {
+    // Interpolates each component; t = 0 and t = 1 always return the exact end scales
+    pub fn interpolate(self, from: Vec3, to: Vec3, t: f32) -> Vec3 {
+        if t <= 0.0 {
+            return from;
+        }
+        if t >= 1.0 {
+            return to;
+        }
+        Vec3::new(
+            self.interpolate_component(from.x, to.x, t),
+            self.interpolate_component(from.y, to.y, t),
+            self.interpolate_component(from.z, to.z, t),
+        )
+    }
+
+    // Coordinates of a scale in the tangent space of a `VSTransform`, where splines sum and scale
+    // it. Each chart follows the same path as `interpolate` and puts a scale of one at zero.
+    // The exponential chart leaves out the sign, which comes back from `mirror`.
+    fn scale_to_tangent(self, scale: Vec3) -> Vec3 {
+        let chart = |s: f32| match self {
+            ScaleInterpolation::Exponential => s.abs().max(f32::MIN_POSITIVE).ln(),
+            ScaleInterpolation::Linear => s - 1.0,
+            ScaleInterpolation::SignedLog => s.signum() * s.abs().ln_1p() - LN_2,
+        };
+        Vec3::new(chart(scale.x), chart(scale.y), chart(scale.z))
+    }
+
+    fn scale_from_tangent(self, coordinates: Vec3, mirror: Vec3) -> Vec3 {
+        let chart = |c: f32, mirror: f32| match self {
+            ScaleInterpolation::Exponential if mirror < 0.0 => -c.exp(),
+            ScaleInterpolation::Exponential => c.exp(),
+            ScaleInterpolation::Linear => c + 1.0,
+            ScaleInterpolation::SignedLog => {
+                let log = c + LN_2;
+                log.signum() * log.abs().exp_m1()
+            }
+        };
+        Vec3::new(chart(coordinates.x, mirror.x), chart(coordinates.y, mirror.y), chart(coordinates.z, mirror.z))
+    }
+
+    fn interpolate_component(self, from: f32, to: f32, t: f32) -> f32 {
+        match self {
+            ScaleInterpolation::Exponential if from * to > 0.0 => {
+                let log = (1. - t) * from.abs().log2() + t * to.abs().log2();
+                from.signum() * log.exp2()
+            }
+            ScaleInterpolation::Exponential | ScaleInterpolation::Linear => from + (to - from) * t,
+            ScaleInterpolation::SignedLog => {
+                let signed_log = |s: f32| s.signum() * s.abs().ln_1p();
+                let log = (1. - t) * signed_log(from) + t * signed_log(to);
+                log.signum() * log.abs().exp_m1()
+            }
+        }
+    }
+}
+
+// Wrapper type for Transform. It holds the transform's coordinates in the tangent space of the
+// transform group (see `transform_blend`), so sums and scalings of transforms are consistent,
+// `ZERO` really is the identity, and splines through rotations of more than half a turn don't
+// wrap between operations. `transform` maps the coordinates back once a value is sampled. The
+// scale interpolation picks how `lerp` moves the scale and the chart its coordinates are in.
+// Values that don't set one, like `ZERO` and converted transforms, take it from the values they
+// are combined with, so a spline keeps the mode of its keys. The signs of the scale are summed
+// and scaled along with the coordinates, for charts that lose them.
+#[derive(Clone, Reflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+#[serde(from = "TransformKey", into = "TransformKey")]
+pub struct VSTransform {
+    tangent: TransformTangent,
+    mirror: Vec3,
+    scale_interpolation: Option<ScaleInterpolation>,
+}
+
+// Serialised form of `VSTransform`, with named fields so scene files stay readable. The scale
+// interpolation may be left out and falls back to the default.
+#[derive(Serialize, Deserialize)]
+struct TransformKey {
+    translation: Vec3,
+    rotation: Quat,
+    scale: Vec3,
+    #[serde(default)]
+    scale_interpolation: ScaleInterpolation,
+}
+
+impl From<TransformKey> for VSTransform {
+    fn from(key: TransformKey) -> Self {
+        let transform = Transform {
+            translation: key.translation,
+            rotation: key.rotation,
+            scale: key.scale,
+        };
+        VSTransform::new(transform, key.scale_interpolation)
+    }
+}
+
+impl From<VSTransform> for TransformKey {
+    fn from(value: VSTransform) -> Self {
+        let transform = value.transform();
+        TransformKey {
+            translation: transform.translation,
+            rotation: transform.rotation,
+            scale: transform.scale,
+            scale_interpolation: value.scale_interpolation(),
+        }
+    }
+}
+
+impl VSTransform {
+    pub fn new(transform: Transform, scale_interpolation: ScaleInterpolation) -> Self {
+        VSTransform::charted(transform, Some(scale_interpolation))
+    }
+
+    fn charted(transform: Transform, scale_interpolation: Option<ScaleInterpolation>) -> Self {
+        let tangent = TransformTangent {
+            scale: scale_interpolation.unwrap_or_default().scale_to_tangent(transform.scale),
+            ..TransformTangent::log(&Transform { scale: Vec3::ONE, ..transform })
+        };
+        let mirror = Vec3::select(transform.scale.cmplt(Vec3::ZERO), Vec3::NEG_ONE, Vec3::ONE);
+        VSTransform { tangent, mirror, scale_interpolation }
+    }
+
+    pub fn transform(&self) -> Transform {
+        Transform {
+            scale: self.scale_interpolation().scale_from_tangent(self.tangent.scale, self.mirror),
+            ..TransformTangent { scale: Vec3::ZERO, ..self.tangent }.exp()
+        }
+    }
+
+    pub fn scale_interpolation(&self) -> ScaleInterpolation {
+        self.scale_interpolation.unwrap_or_default()
+    }
+
+    #[allow(dead_code)]
+    pub fn with_scale_interpolation(self, scale_interpolation: ScaleInterpolation) -> Self {
+        VSTransform {
+            tangent: self.in_chart(Some(scale_interpolation)),
+            scale_interpolation: Some(scale_interpolation),
+            ..self
+        }
+    }
+
+    // The same transform turned to `rotation`, for values that are sampled already
+    pub fn with_rotation(self, rotation: Quat) -> Self {
+        VSTransform::charted(Transform { rotation, ..self.transform() }, self.scale_interpolation)
+    }
+
+    // Coordinates with the scale in the chart of `scale_interpolation`
+    fn in_chart(&self, scale_interpolation: Option<ScaleInterpolation>) -> TransformTangent {
+        let (from, to) = (self.scale_interpolation(), scale_interpolation.unwrap_or_default());
+        if from == to {
+            return self.tangent;
+        }
+        TransformTangent {
+            scale: to.scale_to_tangent(from.scale_from_tangent(self.tangent.scale, self.mirror)),
+            ..self.tangent
+        }
+    }
+
+    // This transform scaled by `factor` in the tangent space, keeping its settings
+    fn scaled(&self, factor: f32) -> Self {
+        VSTransform {
+            tangent: self.tangent * factor,
+            mirror: self.mirror * factor,
+            ..*self
+        }
+    }
+
+    // This plus `other` times `factor` in the tangent space, in the chart of the first of them
+    // that sets a scale interpolation
+    fn combine(&self, other: &Self, factor: f32) -> Self {
+        let scale_interpolation = self.scale_interpolation.or(other.scale_interpolation);
+        VSTransform {
+            tangent: self.in_chart(scale_interpolation) + other.in_chart(scale_interpolation) * factor,
+            mirror: self.mirror + other.mirror * factor,
+            scale_interpolation,
+        }
+    }
+}
+
+// Takes the tangent's scale to be the log of the scale, as `TransformTangent::log` makes it
+impl From<TransformTangent> for VSTransform {
+    fn from(tangent: TransformTangent) -> Self {
+        VSTransform {
+            tangent,
+            mirror: Vec3::ONE,
+            scale_interpolation: None,
+        }
+    }
+}
 
 impl Div<f32> for VSTransform {
     type Output = Self;
 
     fn div(self, rhs: f32) -> Self::Output {
-        VSTransform(Transform {
-            translation: self.0.translation / rhs,
-            rotation: self.0.rotation / rhs, // For simplicity, assuming rotation can be divided by scalar.
-            scale: self.0.scale / rhs,
-        })
+        self.scaled(1.0 / rhs)
     }
 }
 
@@ -25,11 +227,7 @@ impl Add for VSTransform {
     type Output = Self;
 
     fn add(self, rhs: Self) -> Self::Output {
-        VSTransform(Transform {
-            translation: self.0.translation + rhs.0.translation,
-            rotation: self.0.rotation * rhs.0.rotation, // Combining rotations.
-            scale: self.0.scale + rhs.0.scale,
-        })
+        self.combine(&rhs, 1.0)
     }
 }
 
@@ -37,11 +235,7 @@ impl Sub for VSTransform {
     type Output = Self;
 
     fn sub(self, rhs: Self) -> Self::Output {
-        VSTransform(Transform {
-            translation: self.0.translation - rhs.0.translation,
-            rotation: self.0.rotation * rhs.0.rotation.conjugate(),
-            scale: self.0.scale - rhs.0.scale,
-        })
+        self.combine(&rhs, -1.0)
     }
 }
 
@@ -49,26 +243,24 @@ impl Neg for VSTransform {
     type Output = Self;
 
     fn neg(self) -> Self::Output {
-        VSTransform(Transform {
-            translation: -self.0.translation,
-            rotation: self.0.rotation.conjugate(), // Conjugate for negating rotation.
-            scale: -self.0.scale,
-        })
+        self.scaled(-1.0)
     }
 }
 
 impl Default for VSTransform {
     fn default() -> Self {
-        VSTransform(Transform::IDENTITY)
+        VSTransform::ZERO
     }
 }
 
 impl Debug for VSTransform {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let transform = self.transform();
         f.debug_struct("VSTransform")
-            .field("translation", &self.0.translation)
-            .field("rotation", &self.0.rotation)
-            .field("scale", &self.0.scale)
+            .field("translation", &transform.translation)
+            .field("rotation", &transform.rotation)
+            .field("scale", &transform.scale)
+            .field("scale_interpolation", &self.scale_interpolation())
             .finish()
     }
 }
@@ -77,11 +269,7 @@ impl Mul<f32> for VSTransform {
     type Output = Self;
 
     fn mul(self, rhs: f32) -> Self::Output {
-        VSTransform(Transform {
-            translation: self.0.translation * rhs,
-            rotation: self.0.rotation * rhs, // Assuming scalar multiplication for rotation.
-            scale: self.0.scale * rhs,
-        })
+        self.scaled(rhs)
     }
 }
 
@@ -89,43 +277,178 @@ impl Copy for VSTransform {}
 
 impl VectorSpace for VSTransform {
 
-    const ZERO: Self = VSTransform(Transform::IDENTITY);
+    const ZERO: Self = VSTransform {
+        tangent: TransformTangent::ZERO,
+        mirror: Vec3::ZERO,
+        scale_interpolation: None,
+    };
 
+    // Interpolates each part of the transforms on its own, so translations move in straight lines
     fn lerp(&self, other: VSTransform, t: f32) -> Self {
-        let translation = self.0.translation.lerp(other.0.translation, t);
-        let rotation = self.0.rotation.slerp(other.0.rotation, t);
-        let scale = elerp(self.0.scale, other.0.scale, t);
-
-        VSTransform(
-            Transform{
-                translation,
-                rotation,
-                scale,
-            }
-        )
+        let scale_interpolation = self.scale_interpolation.or(other.scale_interpolation);
+        let (from, to) = (self.transform(), other.transform());
+        let transform = Transform {
+            translation: from.translation.lerp(to.translation, t),
+            rotation: from.rotation.slerp(to.rotation, t),
+            scale: scale_interpolation.unwrap_or_default().interpolate(from.scale, to.scale, t),
+        };
+        VSTransform::charted(transform, scale_interpolation)
     }
 }
 
-fn elerp(v1: Vec3, v2: Vec3, t: f32) -> Vec3 {
-    let x_factor_log = (1. - t) * v1.x.log2() + t * v2.x.log2();
-    let y_factor_log = (1. - t) * v1.y.log2() + t * v2.y.log2();
-    let z_factor_log = (1. - t) * v1.z.log2() + t * v2.z.log2();
-
-    Vec3::new(
-        x_factor_log.exp2(),
-        y_factor_log.exp2(),
-        z_factor_log.exp2(),
-    )
-}
-
 impl From<Transform> for VSTransform {
     fn from(transform: Transform) -> Self {
-        VSTransform(transform)
+        VSTransform::charted(transform, None)
     }
 }
 
 impl Into<Transform> for VSTransform {
     fn into(self) -> Transform {
-        self.0
+        self.transform()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+    use bevy::math::VectorSpace;
+    use bevy::prelude::{Quat, Transform, Vec3};
+    use bevy::scene::ron;
+    use serde::Deserialize;
+    use crate::keyframes::{Curve, KeyframingComponent};
+    use super::{ScaleInterpolation, VSTransform};
+
+    fn assert_close(a: Transform, b: Transform) {
+        assert!(a.translation.abs_diff_eq(b.translation, 1e-4), "{a:?} != {b:?}");
+        // q and -q are the same rotation
+        assert!(a.rotation.dot(b.rotation).abs() > 1.0 - 1e-5, "{a:?} != {b:?}");
+        assert!(a.scale.abs_diff_eq(b.scale, 1e-4), "{a:?} != {b:?}");
+    }
+
+    fn turned(angle: f32, translation: Vec3) -> Transform {
+        Transform::from_translation(translation).with_rotation(Quat::from_rotation_y(angle))
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn log_exp_round_trip() {
+        let axes = [Vec3::X, Vec3::Y, Vec3::new(1.0, -2.0, 0.5).normalize()];
+        let angles = [0.0, 1e-5, 0.5, PI / 2.0, PI - 1e-3, PI, PI + 1e-3, 1.9 * PI];
+        for axis in axes {
+            for angle in angles {
+                let transform = Transform {
+                    translation: Vec3::new(1.5, -0.25, 3.0),
+                    rotation: Quat::from_axis_angle(axis, angle),
+                    scale: Vec3::new(0.5, 2.0, 1.0),
+                };
+                assert_close(VSTransform::from(transform).transform(), transform);
+            }
+        }
+    }
+
+    #[test]
+    fn arithmetic_past_half_a_turn() {
+        let turn = VSTransform::from(turned(2.0, Vec3::new(1.0, 0.0, 2.0)));
+        assert_close(((turn + turn) - turn).transform(), turn.transform());
+        assert_close((turn * 2.0 / 2.0).transform(), turn.transform());
+        assert_close((VSTransform::ZERO + turn).transform(), turn.transform());
+    }
+
+    #[test]
+    fn spline_near_half_a_turn() {
+        // Sums in the spline's coefficients and its mirrored ends turn past half a turn
+        let keys: Vec<Transform> = [2.8, 2.9, 3.0, 3.1]
+            .iter()
+            .enumerate()
+            .map(|(index, angle)| turned(*angle, Vec3::new(index as f32, 0.0, -2.0)))
+            .collect();
+        let curve = Curve::new(0.5, keys.iter().copied().map(VSTransform::from).collect());
+        for (time, key) in curve.key_times().zip(&keys) {
+            assert_close(curve.sample(time).transform(), *key);
+        }
+        for step in 0..=60 {
+            let time = step as f32 / 60.0;
+            let translation = curve.sample(time).transform().translation;
+            let expected = Vec3::new(3.0 * time, 0.0, -2.0);
+            assert!(translation.abs_diff_eq(expected, 0.05), "{time}: {translation} != {expected}");
+        }
+    }
+
+    #[test]
+    fn scale_keeps_its_mode_through_arithmetic() {
+        let scales = [Vec3::new(0.0, -1.0, 2.0), Vec3::new(-0.5, 0.0, 1.0)];
+        for mode in [ScaleInterpolation::Linear, ScaleInterpolation::SignedLog] {
+            let keys: Vec<VSTransform> = scales
+                .iter()
+                .map(|scale| VSTransform::new(Transform::from_scale(*scale), mode))
+                .collect();
+            for (key, scale) in keys.iter().zip(scales) {
+                assert_close(key.transform(), Transform::from_scale(scale));
+                assert_close(((*key + *key) - *key).transform(), key.transform());
+            }
+            let curve = Curve::new(0.5, keys.clone());
+            for step in 0..=10 {
+                let value = curve.sample(step as f32 / 10.0);
+                assert_eq!(value.scale_interpolation(), mode);
+                assert!(value.transform().scale.is_finite());
+            }
+            assert_close(curve.sample(1.0).transform(), keys[1].transform());
+        }
+    }
+
+    #[test]
+    fn mirrored_scale_survives_the_spline() {
+        // The default chart takes the log of the scale, which has to leave its sign alone
+        let scales = [Vec3::ONE, Vec3::new(-2.0, 1.0, 1.0), Vec3::new(-1.0, 0.5, -1.0), Vec3::new(-1.5, 1.0, -2.0)];
+        let keys: Vec<VSTransform> = scales.iter().map(|scale| VSTransform::from(Transform::from_scale(*scale))).collect();
+        for (key, scale) in keys.iter().zip(scales) {
+            assert_close(key.transform(), Transform::from_scale(scale));
+            assert_close(((*key + *key) - *key).transform(), Transform::from_scale(scale));
+        }
+        let curve = Curve::new(0.5, keys.clone());
+        for (time, scale) in curve.key_times().zip(scales) {
+            assert_close(curve.sample(time).transform(), Transform::from_scale(scale));
+        }
+        // Between keys that are both mirrored the scale stays mirrored
+        for step in 0..=10 {
+            let time = 2.0 / 3.0 + step as f32 / 30.0;
+            assert!(curve.sample(time).transform().scale.x < 0.0, "{time}");
+        }
+        // Pose blends lerp, which has to keep the sign too, and goes through zero where it changes
+        let blended = keys[1].lerp(keys[2], 0.5).transform().scale;
+        assert!(blended.abs_diff_eq(Vec3::new(-(2.0f32).sqrt(), 0.5f32.sqrt(), 0.0), 1e-4), "{blended}");
+    }
+
+    #[test]
+    fn keyframing_component_round_trips_through_ron() {
+        #[derive(Deserialize)]
+        struct Saved {
+            curve: SavedCurve,
+        }
+        #[derive(Deserialize)]
+        struct SavedCurve {
+            tension: f32,
+        }
+
+        let keys = vec![
+            VSTransform::new(turned(0.5, Vec3::X).with_scale(Vec3::new(2.0, -1.0, 0.5)), ScaleInterpolation::SignedLog),
+            VSTransform::new(turned(2.9, Vec3::Y).with_scale(Vec3::new(0.5, 0.0, 1.5)), ScaleInterpolation::SignedLog),
+            VSTransform::new(turned(-1.0, Vec3::Z).with_scale(Vec3::splat(3.0)), ScaleInterpolation::SignedLog),
+        ];
+        let component = KeyframingComponent::new(0.3, keys.clone());
+        let text = ron::to_string(&component).unwrap();
+        let loaded: KeyframingComponent<VSTransform> = ron::from_str(&text).unwrap();
+
+        assert_eq!(ron::from_str::<Saved>(&text).unwrap().curve.tension, 0.3);
+        assert_eq!(ron::from_str::<Saved>(&ron::to_string(&loaded).unwrap()).unwrap().curve.tension, 0.3);
+        // Keys go through their log coordinates and back, so they only come back close
+        assert_eq!(loaded.curve.keys().len(), keys.len());
+        for (loaded, key) in loaded.curve.keys().iter().zip(&keys) {
+            assert_close(loaded.transform(), key.transform());
+            assert_eq!(loaded.scale_interpolation(), key.scale_interpolation());
+        }
+        for step in 0..=10 {
+            let time = step as f32 / 10.0;
+            assert_close(loaded.sample(time).transform(), component.sample(time).transform());
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use std::ops::{Add, Div, Mul, Neg, Sub};
+use bevy::math::{Vec2, VectorSpace};
+use bevy::prelude::{EulerRot, Quat, Reflect, ReflectDeserialize, ReflectSerialize, Transform};
+use serde::{Deserialize, Serialize};
+
+// 2D counterpart of VSTransform. The rotation is a plain angle in radians around Z rather than
+// a quaternion, so it is a real vector space and interpolating from 0 to 4π spins twice.
+#[derive(Clone, Copy, Debug, PartialEq, Reflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub struct VSTransform2d {
+    pub translation: Vec2,
+    pub angle: f32,
+    pub scale: Vec2,
+}
+
+impl VSTransform2d {
+    pub const IDENTITY: Self = VSTransform2d {
+        translation: Vec2::ZERO,
+        angle: 0.0,
+        scale: Vec2::ONE,
+    };
+
+    #[allow(dead_code)]
+    pub fn new(translation: Vec2, angle: f32, scale: Vec2) -> Self {
+        VSTransform2d {
+            translation,
+            angle,
+            scale,
+        }
+    }
+
+    // Writes into a 3D transform, keeping its depth and Z scale
+    pub fn apply(&self, transform: &mut Transform) {
+        transform.translation = self.translation.extend(transform.translation.z);
+        transform.rotation = Quat::from_rotation_z(self.angle);
+        transform.scale = self.scale.extend(transform.scale.z);
+    }
+}
+
+impl Default for VSTransform2d {
+    fn default() -> Self {
+        VSTransform2d::IDENTITY
+    }
+}
+
+impl Add for VSTransform2d {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        VSTransform2d {
+            translation: self.translation + rhs.translation,
+            angle: self.angle + rhs.angle,
+            scale: self.scale + rhs.scale,
+        }
+    }
+}
+
+impl Sub for VSTransform2d {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        VSTransform2d {
+            translation: self.translation - rhs.translation,
+            angle: self.angle - rhs.angle,
+            scale: self.scale - rhs.scale,
+        }
+    }
+}
+
+impl Neg for VSTransform2d {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        VSTransform2d {
+            translation: -self.translation,
+            angle: -self.angle,
+            scale: -self.scale,
+        }
+    }
+}
+
+impl Mul<f32> for VSTransform2d {
+    type Output = Self;
+
+    fn mul(self, rhs: f32) -> Self::Output {
+        VSTransform2d {
+            translation: self.translation * rhs,
+            angle: self.angle * rhs,
+            scale: self.scale * rhs,
+        }
+    }
+}
+
+impl Div<f32> for VSTransform2d {
+    type Output = Self;
+
+    fn div(self, rhs: f32) -> Self::Output {
+        VSTransform2d {
+            translation: self.translation / rhs,
+            angle: self.angle / rhs,
+            scale: self.scale / rhs,
+        }
+    }
+}
+
+impl VectorSpace for VSTransform2d {
+    const ZERO: Self = VSTransform2d {
+        translation: Vec2::ZERO,
+        angle: 0.0,
+        scale: Vec2::ZERO,
+    };
+}
+
+// Only the Z rotation survives, wrapped to (-π, π]
+impl From<Transform> for VSTransform2d {
+    fn from(transform: Transform) -> Self {
+        let (angle, _, _) = transform.rotation.to_euler(EulerRot::ZYX);
+        VSTransform2d {
+            translation: transform.translation.truncate(),
+            angle,
+            scale: transform.scale.truncate(),
+        }
+    }
+}
+
+// Continuous frame position in a texture atlas. Tweening it as a float lets curves and easing
+// apply to flipbooks; it is rounded to the nearest frame when written to `TextureAtlas::index`.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Reflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub struct AtlasFrame(pub f32);
+
+impl AtlasFrame {
+    pub fn index(&self, len: usize) -> usize {
+        (self.0.round().max(0.0) as usize).min(len.saturating_sub(1))
+    }
+}
+
+impl Add for AtlasFrame {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        AtlasFrame(self.0 + rhs.0)
+    }
+}
+
+impl Sub for AtlasFrame {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        AtlasFrame(self.0 - rhs.0)
+    }
+}
+
+impl Neg for AtlasFrame {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        AtlasFrame(-self.0)
+    }
+}
+
+impl Mul<f32> for AtlasFrame {
+    type Output = Self;
+
+    fn mul(self, rhs: f32) -> Self::Output {
+        AtlasFrame(self.0 * rhs)
+    }
+}
+
+impl Div<f32> for AtlasFrame {
+    type Output = Self;
+
+    fn div(self, rhs: f32) -> Self::Output {
+        AtlasFrame(self.0 / rhs)
+    }
+}
+
+impl VectorSpace for AtlasFrame {
+    const ZERO: Self = AtlasFrame(0.0);
+}
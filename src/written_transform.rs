@@ -0,0 +1,27 @@
+//! Telling a system's own output apart from new input when it adjusts transforms in place.
+//!
+//! A system that adjusts a transform every frame reads back what it wrote last frame when
+//! nothing else set the transform since, and adjusting that again makes the entity creep on.
+//! A `WrittenTransform` keeps the transform the system started from and the one it wrote, and
+//! hands back the former for as long as the entity still has the latter.
+
+use bevy::prelude::*;
+
+#[derive(Clone, Copy, Default, Debug)]
+pub(crate) struct WrittenTransform {
+    written: Option<(Transform, Transform)>,
+}
+
+impl WrittenTransform {
+    // Transform to start from, given the one the entity has now
+    pub(crate) fn input(&self, current: Transform) -> Transform {
+        match self.written {
+            Some((input, output)) if output == current => input,
+            _ => current,
+        }
+    }
+
+    pub(crate) fn write(&mut self, input: Transform, output: Transform) {
+        self.written = Some((input, output));
+    }
+}